@@ -16,29 +16,196 @@
 //  limitations under the License.
 //////////////////////////////////////////////////////////////////////////////
 
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::thread::sleep;
+#[cfg(feature = "threaded")]
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
 
+use winit::event::ElementState;
 use winit::event_loop::ControlFlow;
+use winit::monitor::MonitorHandle;
 use winit::platform::run_return::EventLoopExtRunReturn;
 
 pub use winit::{
-    event::WindowEvent as Event,
-    event_loop::EventLoop,
-    window::{Window, WindowBuilder},
+    dpi::{PhysicalSize, Size},
+    event::{DeviceEvent, DeviceId, WindowEvent as Event},
+    event_loop::{EventLoop, EventLoopProxy},
+    monitor::VideoMode,
+    window::{CursorIcon, Fullscreen, Window, WindowBuilder, WindowId},
 };
 
+use crate::commands::Commands;
+use crate::config::{
+    CatchupStrategy, DuplicateStates, EventMode, Phase, PhaseEdge, PhaseOrder, RenderCap,
+    RenderMode, RunConfig,
+};
 use crate::error::{AppError, MaybeResult};
+use crate::input::{ActionEvent, InputMap, Key, KeyTracker, ScanCode};
 use crate::state::{Action, State};
+use crate::window::{AsWindow, ResizeSource, WindowOps};
+
+const STATS_FRAMES: usize = 60;
+pub(crate) const DEFAULT_TICK_RATE: u32 = 60;
+
+type RenderFn<D, W> = Box<dyn FnOnce(&Data<D, W>)>;
+
+#[cfg(feature = "threaded")]
+type AppThread<E1, E2> = Result<JoinHandle<Result<(), AppError<E1, E2>>>, AppError<E1, E2>>;
 
-pub struct App<D, W> {
-    event_loop: EventLoop<()>,
+pub struct App<D, W, T: 'static = ()> {
+    event_loop: EventLoop<T>,
     data: Data<D, W>,
+    deferred_events: VecDeque<(WindowId, Event<'static>)>,
+    tick_rate: u32,
 }
 
 pub struct Data<D, W> {
     window: W,
+    window_id: Option<WindowId>,
+    windows: HashMap<WindowId, Window>,
+    pending_windows: Vec<WindowBuilder>,
+    closed_windows: Vec<WindowId>,
     pub data: D,
+
+    fps: u32,
+    fps_override: Option<FpsOverride>,
+    // The active state's own tick rate, as of the last frame.
+    state_rate: Option<u32>,
+    event_mode: EventMode,
+
+    inner_size: PhysicalSize<u32>,
+    requested_size: Option<PhysicalSize<u32>>,
+    coalesce_resizes: bool,
+    pending_resize: Option<(PhysicalSize<u32>, ResizeSource)>,
+    focused: bool,
+    cursor_icon: Cell<CursorIcon>,
+    keys: KeyTracker,
+    input_map: InputMap,
+    scale_factor: f64,
+    ui_zoom: f32,
+    content_scale_changed: bool,
+
+    accum: Duration,
+    spf: Duration,
+    tick_hashes: Vec<u64>,
+    pub(crate) prev_frame: Instant,
+    tick_delta: Duration,
+    time_in_state: Duration,
+    step_key: Option<Key>,
+    duplicate_states: Option<DuplicateStates>,
+    step_requested: bool,
+    tick_channels: Vec<TickChannel>,
+
+    render_queue: Vec<RenderFn<D, W>>,
+    // Additional windows with something new to show, see `render`.
+    window_redraws: HashSet<WindowId>,
+    redrawing: Vec<WindowId>,
+    // Applies queued `WindowOps` before rendering. Set by `App::start`, and
+    // a no-op for `HeadlessApp`, which has no window to apply them to.
+    apply_ops: fn(&mut Data<D, W>),
+    phase: Option<Phase>,
+    window_ops: WindowOps,
+    dirty: bool,
+    redraw_requested: Cell<bool>,
+    redraw_event: bool,
+    rendering_enabled: bool,
+
+    quit: bool,
+    quit_reason: Option<&'static str>,
+    device_lost: Cell<bool>,
+    render_failed: Cell<bool>,
+    restart: bool,
+
+    frame: u64,
+    pub(crate) frame_start: Instant,
+    frame_ticks: VecDeque<u32>,
+    ticks_this_frame: u32,
+
+    pending_input: Option<Instant>,
+    state_timings: Vec<(&'static str, Duration)>,
+    input_latencies: VecDeque<Duration>,
+}
+
+/// A run of the loop driven one frame at a time, see `App::start`.
+pub struct Stepper<D, W, S> {
+    pub(crate) config: RunConfig<D, W>,
+    pub(crate) state: S,
+
+    // The stack the run started with, top last, restored on restart.
+    initial: Vec<S>,
+
+    // The states below the active one, pushed through `Action::Push`.
+    pub(crate) stack: Vec<S>,
+
+    pub(crate) rate: Option<u32>,
+    alpha: f64,
+    pub(crate) finished: bool,
+}
+
+/// The progress of a run, taken by `App::snapshot` and put back by
+/// `App::restore`, e.g. to save a game mid-level. With the `serde` feature it
+/// can be serialized whenever the state type can.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoopSnapshot<S> {
+    /// The active state.
+    pub state: S,
+
+    /// The states below the active one, top last.
+    pub stack: Vec<S>,
+
+    /// Time accumulated towards the next tick.
+    pub accum: Duration,
+
+    /// The number of frames run so far.
+    pub frame: u64,
+
+    /// Time simulated in the active state.
+    pub time_in_state: Duration,
+}
+
+pub struct AppBuilder<WindowInit, DataInit> {
+    window_init: WindowInit,
+    data_init: DataInit,
+    tick_rate: u32,
+}
+
+pub struct WindowedBuilder {
+    builder: WindowBuilder,
+}
+
+struct TickChannel {
+    name: &'static str,
+    spf: Duration,
+    accum: Duration,
+}
+
+#[derive(Copy, Clone)]
+struct FpsOverride {
+    fps: u32,
+    until: Option<Instant>,
+}
+
+impl App<(), ()> {
+    /// Starts building an app whose window is configured declaratively. The
+    /// window init closure passed to `WindowedBuilder::build` receives the
+    /// configured `WindowBuilder` to finish off, e.g. by creating a surface.
+    pub fn windowed() -> WindowedBuilder {
+        WindowedBuilder {
+            builder: WindowBuilder::new(),
+        }
+    }
+
+    /// Starts building an app from its init closures and loop settings.
+    pub fn builder() -> AppBuilder<(), ()> {
+        AppBuilder {
+            window_init: (),
+            data_init: (),
+            tick_rate: DEFAULT_TICK_RATE,
+        }
+    }
 }
 
 impl<D, W> App<D, W> {
@@ -52,75 +219,1791 @@ impl<D, W> App<D, W> {
         WindowInit: FnOnce(&EventLoop<()>) -> R1,
         DataInit: FnOnce(&W) -> R2,
     {
-        let event_loop = EventLoop::new();
+        let event_loop = create_event_loop(EventLoop::new)?;
+        Self::from_event_loop(event_loop, f, g)
+    }
+}
+
+#[cfg(feature = "threaded")]
+impl<D: 'static, W: AsWindow + 'static> App<D, W> {
+    /// Builds the app and runs it with `state` on a new thread, leaving the
+    /// calling thread free. The window and user data are created on the
+    /// loop's thread and never leave it, so only the init closures and the
+    /// state need to be `Send`. The handle yields any error from building
+    /// the app once the loop has finished.
+    ///
+    /// Only Windows and the X11/Wayland platforms can run the event loop
+    /// off the main thread. Elsewhere, notably macOS, this fails straight
+    /// away with `AppError::UnsupportedPlatform`.
+    pub fn run_on_thread<WindowInit, DataInit, R1, R2, S>(
+        f: WindowInit,
+        g: DataInit,
+        fps: u32,
+        state: S,
+    ) -> AppThread<R1::Error, R2::Error>
+    where
+        R1: MaybeResult<W>,
+        R2: MaybeResult<D>,
+        R1::Error: Send + 'static,
+        R2::Error: Send + 'static,
+        WindowInit: FnOnce(&EventLoop<()>) -> R1 + Send + 'static,
+        DataInit: FnOnce(&W) -> R2 + Send + 'static,
+        S: State<D, W> + Send + 'static,
+    {
+        let new_any_thread = threaded::new_any_thread().ok_or(AppError::UnsupportedPlatform)?;
+
+        Ok(std::thread::spawn(move || {
+            let event_loop = create_event_loop(new_any_thread)?;
+            Self::from_event_loop(event_loop, f, g)?.run(fps, state);
+            Ok(())
+        }))
+    }
+}
+
+impl<D, W, T: 'static> App<D, W, T> {
+    /// Like `new`, but the event loop carries user events of type `T`, sent
+    /// from other threads through `create_proxy` and passed to
+    /// `handle_user_event`.
+    pub fn with_user_event<WindowInit, DataInit, R1, R2>(
+        f: WindowInit,
+        g: DataInit,
+    ) -> Result<Self, AppError<R1::Error, R2::Error>>
+    where
+        R1: MaybeResult<W>,
+        R2: MaybeResult<D>,
+        WindowInit: FnOnce(&EventLoop<T>) -> R1,
+        DataInit: FnOnce(&W) -> R2,
+    {
+        let event_loop = create_event_loop(EventLoop::with_user_event)?;
+        Self::from_event_loop(event_loop, f, g)
+    }
+
+    /// Builds the app on an existing event loop, e.g. one created with
+    /// platform-specific options.
+    pub fn from_event_loop<WindowInit, DataInit, R1, R2>(
+        event_loop: EventLoop<T>,
+        f: WindowInit,
+        g: DataInit,
+    ) -> Result<Self, AppError<R1::Error, R2::Error>>
+    where
+        R1: MaybeResult<W>,
+        R2: MaybeResult<D>,
+        WindowInit: FnOnce(&EventLoop<T>) -> R1,
+        DataInit: FnOnce(&W) -> R2,
+    {
         let window = f(&event_loop).as_result().map_err(AppError::WindowError)?;
         let data = g(&window).as_result().map_err(AppError::DataError)?;
 
         Ok(App {
             event_loop,
-            data: Data { window, data },
+            data: Data::new(window, data),
+            deferred_events: VecDeque::new(),
+            tick_rate: DEFAULT_TICK_RATE,
         })
     }
 
-    fn handle_events<S: State<D, W>>(&mut self, mut state: S) -> Option<S> {
-        let mut quit = false;
+    /// Runs setup that needs mutable access to both the window and the user
+    /// data, e.g. registering the window with the renderer, before `run`.
+    pub fn configure<F: FnOnce(&mut Data<D, W>)>(&mut self, f: F) -> &mut Self {
+        f(&mut self.data);
+        self
+    }
+
+    /// The underlying event loop, for platform setup between `new` and
+    /// `run`. The loop must only be configured here, not run: stateloop
+    /// drives it itself and relies on seeing every event.
+    pub fn event_loop(&self) -> &EventLoop<T> {
+        &self.event_loop
+    }
+
+    /// Mutable access to the underlying event loop, with the same caveats
+    /// as `event_loop`. In particular `run_return` must not be called on it.
+    pub fn event_loop_mut(&mut self) -> &mut EventLoop<T> {
+        &mut self.event_loop
+    }
+
+    /// A handle for sending user events to the loop, e.g. from a loading
+    /// thread. Sending wakes the loop even in `EventMode::Wait`, and the
+    /// event is passed to the active state's `handle_user_event`. For apps
+    /// without a user event type, sending `()` is a plain wake-up.
+    pub fn create_proxy(&self) -> EventLoopProxy<T> {
+        self.event_loop.create_proxy()
+    }
+
+    /// Installs the map used to translate input into `handle_action` calls.
+    pub fn set_input_map(&mut self, map: InputMap) {
+        self.data.input_map = map;
+    }
+
+    pub fn tick_hashes(&self) -> &[u64] {
+        &self.data.tick_hashes
+    }
+
+    fn handle_events<S: State<D, W, T>>(
+        &mut self,
+        state: &mut S,
+        stack: &mut Vec<S>,
+        budget: Option<Duration>,
+        deadline: Option<Instant>,
+    ) -> bool {
+        let start = Instant::now();
+        let over_budget = || budget.is_some_and(|budget| start.elapsed() >= budget);
+
+        while !over_budget() {
+            match self.deferred_events.pop_front() {
+                Some((window, event)) => {
+                    let (name, handled) = (state.name(), Instant::now());
+                    self.data.dispatch_event(state, stack, window, event);
+                    self.data.record_state_time(name, handled.elapsed());
+                }
+                None => break,
+            }
+        }
 
         let event_loop = &mut self.event_loop;
         let data = &mut self.data;
+        let deferred_events = &mut self.deferred_events;
 
-        event_loop.run_return(|event, _, flow| {
-            *flow = ControlFlow::Exit;
+        let mut wait = data.event_mode != EventMode::Poll && deferred_events.is_empty();
 
-            if let winit::event::Event::WindowEvent {
-                window_id: _,
-                event,
-            } = event
-            {
-                state = match state.handle_event(data, event) {
-                    Action::Continue => state,
-                    Action::Done(state) => state,
-                    Action::Quit => {
-                        quit = true;
-                        state
+        event_loop.run_return(|event, target, flow| {
+            for builder in data.pending_windows.drain(..) {
+                if let Ok(window) = builder.build(target) {
+                    data.windows.insert(window.id(), window);
+                }
+            }
+
+            // Timed per event, so the time spent blocked between them, waiting
+            // for the next, isn't counted towards the state.
+            let (name, handled) = (state.name(), Instant::now());
+
+            match event {
+                winit::event::Event::WindowEvent { window_id, event } => {
+                    wait = false;
+                    data.stamp_input(&event);
+
+                    // Scale factor changes borrow from winit and can't be
+                    // deferred, so they are always handled straight away.
+                    let deferrable = !matches!(event, Event::ScaleFactorChanged { .. });
+
+                    if deferrable && (!deferred_events.is_empty() || over_budget()) {
+                        deferred_events.extend(event.to_static().map(|event| (window_id, event)));
+                    } else {
+                        data.dispatch_event(state, stack, window_id, event);
+                    }
+                }
+                winit::event::Event::DeviceEvent { device_id, event } => {
+                    wait = false;
+
+                    let action = state.handle_device_event(data, device_id, event);
+                    data.apply(state, stack, action);
+                }
+                winit::event::Event::UserEvent(event) => {
+                    wait = false;
+                    data.dispatch_user_event(state, stack, event);
+                }
+                winit::event::Event::RedrawRequested(window_id) => {
+                    wait = false;
+
+                    if data.window_id == Some(window_id) {
+                        data.dirty = true;
+                        data.redraw_event = true;
+                    } else {
+                        data.window_redraws.insert(window_id);
                     }
                 }
+                _ => (),
             }
+
+            data.record_state_time(name, handled.elapsed());
+
+            *flow = match deadline {
+                _ if !wait => ControlFlow::Exit,
+                Some(deadline) if Instant::now() >= deadline => ControlFlow::Exit,
+                Some(deadline) => ControlFlow::WaitUntil(deadline),
+                None => ControlFlow::Wait,
+            };
         });
 
-        if quit {
-            None
-        } else {
-            Some(state)
-        }
+        !std::mem::take(&mut self.data.quit)
+    }
+}
+
+impl<D, W: AsWindow, T: 'static> App<D, W, T> {
+    /// Runs the loop at the tick rate set through `AppBuilder::tick_rate`,
+    /// or 60 ticks a second for apps built otherwise.
+    pub fn launch<S: State<D, W, T>>(&mut self, state: S) -> S {
+        self.run(self.tick_rate, state)
+    }
+
+    /// Runs the loop until a state quits, returning the state that was
+    /// active at the time.
+    ///
+    /// # Panics
+    ///
+    /// If `fps` is zero, as for `RunConfig::new`.
+    pub fn run<S: State<D, W, T>>(&mut self, fps: u32, state: S) -> S {
+        self.run_with(RunConfig::new(fps), state)
+    }
+
+    pub fn run_with<S: State<D, W, T>>(&mut self, config: RunConfig<D, W>, state: S) -> S {
+        self.run_stack_with(config, vec![state])
+    }
+
+    /// Like `run`, but starts with a stack of states, bottom first, e.g. to
+    /// resume a saved game straight into its pause menu. Only the top state
+    /// is entered; the ones below get `on_enter` when popped back to, as if
+    /// each had been pushed over the one before.
+    ///
+    /// # Panics
+    ///
+    /// If `states` is empty.
+    pub fn run_stack<S: State<D, W, T>>(&mut self, fps: u32, states: Vec<S>) -> S {
+        self.run_stack_with(RunConfig::new(fps), states)
+    }
+
+    /// Takes a snapshot of a run's states and timing, see `restore`.
+    pub fn snapshot<S: Clone>(&self, stepper: &Stepper<D, W, S>) -> LoopSnapshot<S> {
+        self.data.snapshot(stepper)
+    }
+
+    /// Puts back a run's states and timing from a snapshot. The active state
+    /// is exited and the restored one entered, and time spent between the
+    /// snapshot and the restore isn't caught up.
+    pub fn restore<S: State<D, W, T>>(
+        &mut self,
+        stepper: &mut Stepper<D, W, S>,
+        snapshot: LoopSnapshot<S>,
+    ) {
+        self.data.restore(stepper, snapshot)
+    }
+
+    pub fn run_stack_with<S: State<D, W, T>>(
+        &mut self,
+        config: RunConfig<D, W>,
+        states: Vec<S>,
+    ) -> S {
+        let mut stepper = self.start_stack(config, states);
+        while self.step(&mut stepper) {}
+
+        self.finish(stepper)
+    }
+
+    /// Sets up a run of the loop without entering it, for hosts that need to
+    /// own the outer loop. Each call to `step` then runs one frame. The host
+    /// can stop calling `step` for a while, e.g. to run a modal native
+    /// dialog, and should call `Data::reset_timing` before resuming so the
+    /// time spent away isn't caught up in a burst of ticks. Once `step`
+    /// returns `false`, `finish` ends the run.
+    pub fn start<S: State<D, W, T>>(
+        &mut self,
+        config: RunConfig<D, W>,
+        state: S,
+    ) -> Stepper<D, W, S> {
+        self.start_stack(config, vec![state])
+    }
+
+    /// Like `start`, with a stack of states as for `run_stack`.
+    pub fn start_stack<S: State<D, W, T>>(
+        &mut self,
+        config: RunConfig<D, W>,
+        states: Vec<S>,
+    ) -> Stepper<D, W, S> {
+        self.data.window_id = Some(self.data.window.as_window().id());
+        self.data.inner_size = self.data.window.as_window().inner_size();
+        self.data.scale_factor = self.data.window.as_window().scale_factor();
+        self.data.apply_ops = Data::apply_window_ops;
+
+        self.data.start(config, states)
     }
 
-    pub fn run<S: State<D, W>>(&mut self, fps: u32, mut state: S) {
-        let mut accum = Duration::from_millis(0);
-        let mut prev = Instant::now();
+    /// Runs one frame of a run set up by `start`. Returns `false` once the
+    /// loop has quit, after which further calls do nothing.
+    pub fn step<S: State<D, W, T>>(&mut self, stepper: &mut Stepper<D, W, S>) -> bool {
+        if !stepper.finished {
+            stepper.finished = !self.frame(stepper);
+        }
+
+        !stepper.finished
+    }
 
-        let spf = Duration::from_millis((1000.0 / fps as f64) as u64);
+    /// Ends a run set up by `start`, calling `on_shutdown` on the active
+    /// state as `run` does once the loop has quit, and returns it.
+    pub fn finish<S: State<D, W, T>>(&mut self, stepper: Stepper<D, W, S>) -> S {
+        self.data.shut_down(stepper)
+    }
 
-        while let Some(next) = self.handle_events(state) {
-            state = next;
-            state.handle_render(&mut self.data);
+    fn frame<S: State<D, W, T>>(&mut self, stepper: &mut Stepper<D, W, S>) -> bool {
+        self.data.frame_start = Instant::now();
 
-            let now = Instant::now();
-            accum += now - prev;
-            prev = now;
+        if self.data.redraw_requested.get() {
+            self.data.window.as_window().request_redraw();
+        }
 
-            while accum >= spf {
-                accum -= spf;
+        let deadline = match self.data.event_mode {
+            EventMode::WaitUntil(interval) => {
+                let wake = self.data.frame_start + interval;
 
-                state.handle_tick(&mut self.data);
+                if stepper.config.ticks_enabled {
+                    let spf = self.data.tick_interval();
+                    let tick = self.data.prev_frame + spf.saturating_sub(self.data.accum);
+                    Some(wake.min(tick))
+                } else {
+                    Some(wake)
+                }
             }
+            _ => None,
+        };
+
+        let Stepper {
+            config,
+            state,
+            stack,
+            ..
+        } = stepper;
 
-            sleep(spf - accum);
+        self.data.phase(config, Phase::Events, PhaseEdge::Start);
+        let running = self.handle_events(state, stack, config.max_event_time, deadline);
+        self.data.phase(config, Phase::Events, PhaseEdge::End);
+
+        if !running || !self.data.finish_frame(stepper, Instant::now()) {
+            return false;
+        }
+
+        let pacing = self
+            .data
+            .frame_pacing(stepper.config.render_cap, self.data.frame_start.elapsed());
+
+        if !pacing.is_zero() {
+            sleep(pacing);
+        }
+
+        true
+    }
+}
+
+impl<D, W, S> Stepper<D, W, S> {
+    /// The active state.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Consumes the stepper, returning the active state, e.g. the one that
+    /// quit once `step` has returned `false`.
+    pub fn into_state(self) -> S {
+        self.state
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+impl<WindowInit, DataInit> AppBuilder<WindowInit, DataInit> {
+    pub fn window_init<F, R1>(self, f: F) -> AppBuilder<F, DataInit>
+    where
+        F: FnOnce(&EventLoop<()>) -> R1,
+    {
+        AppBuilder {
+            window_init: f,
+            data_init: self.data_init,
+            tick_rate: self.tick_rate,
+        }
+    }
+
+    pub fn data_init<G, W, R2>(self, g: G) -> AppBuilder<WindowInit, G>
+    where
+        G: FnOnce(&W) -> R2,
+    {
+        AppBuilder {
+            window_init: self.window_init,
+            data_init: g,
+            tick_rate: self.tick_rate,
+        }
+    }
+
+    /// The tick rate `App::launch` runs at. Defaults to 60.
+    ///
+    /// # Panics
+    ///
+    /// If `rate` is zero.
+    pub fn tick_rate(mut self, rate: u32) -> Self {
+        assert!(rate > 0, "the tick rate must be non-zero");
+        self.tick_rate = rate;
+        self
+    }
+
+    pub fn build<D, W, R1, R2>(self) -> Result<App<D, W>, AppError<R1::Error, R2::Error>>
+    where
+        R1: MaybeResult<W>,
+        R2: MaybeResult<D>,
+        WindowInit: FnOnce(&EventLoop<()>) -> R1,
+        DataInit: FnOnce(&W) -> R2,
+    {
+        if self.tick_rate == 0 {
+            return Err(AppError::InvalidTickRate);
         }
+
+        let mut app = App::new(self.window_init, self.data_init)?;
+        app.tick_rate = self.tick_rate;
+
+        Ok(app)
+    }
+}
+
+impl WindowedBuilder {
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.builder = self.builder.with_title(title);
+        self
+    }
+
+    pub fn inner_size<S: Into<Size>>(mut self, size: S) -> Self {
+        self.builder = self.builder.with_inner_size(size);
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.builder = self.builder.with_resizable(resizable);
+        self
+    }
+
+    pub fn fullscreen(mut self, fullscreen: Option<Fullscreen>) -> Self {
+        self.builder = self.builder.with_fullscreen(fullscreen);
+        self
+    }
+
+    pub fn visible(mut self, visible: bool) -> Self {
+        self.builder = self.builder.with_visible(visible);
+        self
+    }
+
+    pub fn build<D, W, WindowInit, DataInit, R1, R2>(
+        self,
+        f: WindowInit,
+        g: DataInit,
+    ) -> Result<App<D, W>, AppError<R1::Error, R2::Error>>
+    where
+        R1: MaybeResult<W>,
+        R2: MaybeResult<D>,
+        WindowInit: FnOnce(WindowBuilder, &EventLoop<()>) -> R1,
+        DataInit: FnOnce(&W) -> R2,
+    {
+        let builder = self.builder;
+        App::new(move |event_loop| f(builder, event_loop), g)
     }
 }
 
 impl<D, W> Data<D, W> {
+    pub(crate) fn new(window: W, data: D) -> Self {
+        Data {
+            window,
+            window_id: None,
+            windows: HashMap::new(),
+            pending_windows: Vec::new(),
+            closed_windows: Vec::new(),
+            data,
+
+            fps: 0,
+            fps_override: None,
+            state_rate: None,
+            event_mode: EventMode::Poll,
+
+            inner_size: PhysicalSize::new(0, 0),
+            requested_size: None,
+            coalesce_resizes: false,
+            pending_resize: None,
+            focused: false,
+            cursor_icon: Cell::new(CursorIcon::Default),
+            keys: KeyTracker::default(),
+            input_map: InputMap::new(),
+            scale_factor: 1.0,
+            ui_zoom: 1.0,
+            content_scale_changed: false,
+
+            accum: Duration::ZERO,
+            spf: Duration::ZERO,
+            tick_hashes: Vec::new(),
+            prev_frame: Instant::now(),
+            tick_delta: Duration::ZERO,
+            time_in_state: Duration::ZERO,
+            step_key: None,
+            duplicate_states: None,
+            step_requested: false,
+            tick_channels: Vec::new(),
+
+            render_queue: Vec::new(),
+            window_redraws: HashSet::new(),
+            redrawing: Vec::new(),
+            apply_ops: |_| (),
+            phase: None,
+            window_ops: WindowOps::default(),
+            dirty: true,
+            redraw_requested: Cell::new(false),
+            redraw_event: false,
+            rendering_enabled: true,
+
+            quit: false,
+            quit_reason: None,
+            device_lost: Cell::new(false),
+            render_failed: Cell::new(false),
+            restart: false,
+
+            frame: 0,
+            frame_start: Instant::now(),
+            frame_ticks: VecDeque::with_capacity(STATS_FRAMES),
+            ticks_this_frame: 0,
+
+            pending_input: None,
+            state_timings: Vec::new(),
+            input_latencies: VecDeque::with_capacity(STATS_FRAMES),
+        }
+    }
+
     pub fn window(&self) -> &W {
         &self.window
     }
+
+    /// Opens an additional window. It is created the next time the loop
+    /// polls for events, after which it is listed in `windows`, and its
+    /// events reach `handle_event` with its id. A window that fails to open
+    /// is dropped.
+    pub fn open_window(&mut self, builder: WindowBuilder) {
+        self.pending_windows.push(builder);
+    }
+
+    /// Closes a window, calling `handle_window_closed` with its id at the end
+    /// of the frame. Additional windows opened with `open_window` are dropped
+    /// straight away. The main window lives as long as the `App`, so it is
+    /// always the last one open, and closing it quits the loop once the hook
+    /// has run.
+    pub fn close_window(&mut self, id: WindowId) {
+        if self.windows.remove(&id).is_some() {
+            self.closed_windows.push(id);
+        } else if self.window_id == Some(id) {
+            self.closed_windows.push(id);
+            self.quit = true;
+        }
+    }
+
+    /// The additional windows opened with `open_window`.
+    pub fn windows(&self) -> &HashMap<WindowId, Window> {
+        &self.windows
+    }
+
+    /// Mutable access to the window and the user data at the same time.
+    pub fn split_mut(&mut self) -> (&mut W, &mut D) {
+        (&mut self.window, &mut self.data)
+    }
+
+    /// Replaces the frame rate passed to `App::run`. The loop picks it up on
+    /// its next frame. The partial timestep already accumulated is rescaled
+    /// to the new rate, so the change doesn't cause a burst of ticks. An
+    /// active override, or a `tick_rate` returned by the active state, still
+    /// takes precedence; `target_fps` reports the rate actually in use.
+    ///
+    /// # Panics
+    ///
+    /// If `fps` is zero. `override_fps` and `set_temporary_fps` panic likewise.
+    pub fn set_fps(&mut self, fps: u32) {
+        assert!(fps > 0, "the frame rate must be non-zero");
+        self.fps = fps;
+    }
+
+    /// Overrides the frame rate passed to `App::run` until cleared with `None`.
+    pub fn override_fps(&mut self, fps: Option<u32>) {
+        assert!(fps != Some(0), "the frame rate must be non-zero");
+        self.fps_override = fps.map(|fps| FpsOverride { fps, until: None });
+    }
+
+    /// Overrides the frame rate passed to `App::run` for the given duration.
+    /// A duration too long to represent, e.g. `Duration::MAX`, never expires.
+    pub fn set_temporary_fps(&mut self, fps: u32, duration: Duration) {
+        assert!(fps > 0, "the frame rate must be non-zero");
+        self.fps_override = Some(FpsOverride {
+            fps,
+            until: Instant::now().checked_add(duration),
+        });
+    }
+
+    /// Why the loop quit, if it was given a reason, e.g. through
+    /// `Action::QuitBecause`. The reason is also printed to stderr once the
+    /// run ends.
+    pub fn quit_reason(&self) -> Option<&'static str> {
+        self.quit_reason
+    }
+
+    /// Reports that the rendering device was lost. At the end of the frame
+    /// the user data is rebuilt through `RunConfig::data_init` and the
+    /// active state's `handle_device_lost` is called.
+    pub fn report_device_lost(&self) {
+        self.device_lost.set(true);
+    }
+
+    /// Reports that the current render failed, e.g. on a transient swapchain
+    /// error. Once `handle_render` returns it is called again in the same
+    /// frame, up to `RunConfig::render_retries` times, after which the loop
+    /// quits with the reason "render failed".
+    pub fn report_render_failed(&self) {
+        self.render_failed.set(true);
+    }
+
+    /// Restarts the loop at the end of the frame: the user data is rebuilt
+    /// through `RunConfig::data_init` (if set), the state (or stack, for
+    /// `run_stack`) is reset to the one the run was started with, and tick
+    /// timing starts afresh. The window is kept. The active state's `on_exit`
+    /// runs before the data is rebuilt and the initial state's `on_enter`
+    /// after. The new user data is built while the old is still alive, so
+    /// anything that can only exist once (e.g. an exclusive device handle)
+    /// must be released in `on_exit` or before requesting the restart.
+    pub fn request_restart(&mut self) {
+        self.restart = true;
+    }
+
+    /// The label of the key at a physical position under the current
+    /// layout, e.g. for a controls menu. winit doesn't expose the layout, so
+    /// this is only known for keys that have been pressed since the loop
+    /// started.
+    pub fn key_label(&self, scancode: ScanCode) -> Option<String> {
+        self.keys.label(scancode).map(|key| format!("{:?}", key))
+    }
+
+    /// The installed input map, e.g. to rebind controls from a settings menu.
+    pub fn input_map_mut(&mut self) -> &mut InputMap {
+        &mut self.input_map
+    }
+
+    /// The part of the frame the loop is in, or `None` between phases and
+    /// outside of `App::run`.
+    pub fn current_phase(&self) -> Option<Phase> {
+        self.phase
+    }
+
+    /// Collects window changes to make together just before the next render
+    /// phase, rather than one platform call each as they're requested.
+    pub fn window_batch<F: FnOnce(&mut WindowOps)>(&mut self, f: F) {
+        f(&mut self.window_ops);
+    }
+
+    /// Stops or resumes calling `handle_render`, e.g. to keep simulating
+    /// while minimised. Ticks are unaffected. Re-enabling requests a redraw,
+    /// so the next render phase renders whatever the `RenderMode`.
+    pub fn set_rendering_enabled(&mut self, enabled: bool) {
+        if enabled && !self.rendering_enabled {
+            self.request_redraw();
+        }
+
+        self.rendering_enabled = enabled;
+    }
+
+    /// Marks the frame as needing a render under `RenderMode::OnChange`,
+    /// e.g. after a tick that changed something visible.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Asks for the frame to be rendered, like `mark_dirty` but usable from
+    /// anywhere holding the data, and also under `RenderMode::OnRequest`.
+    /// Requests are coalesced, so any number of calls before the next render
+    /// phase result in a single render, and a single `request_redraw` on the
+    /// window, which wakes the loop under `EventMode::Wait`.
+    pub fn request_redraw(&self) {
+        self.redraw_requested.set(true);
+    }
+
+    /// Asks for a window opened with `open_window` to be rendered, under the
+    /// render modes that skip windows with nothing new to show. For the main
+    /// window this is `request_redraw`. Unlike that, it doesn't wake the loop
+    /// under `EventMode::Wait`.
+    pub fn request_window_redraw(&mut self, window: WindowId) {
+        if self.windows.contains_key(&window) {
+            self.window_redraws.insert(window);
+        } else {
+            self.request_redraw();
+        }
+    }
+
+    /// Asks for every window to be rendered on the next frame.
+    pub fn request_redraw_all(&mut self) {
+        self.request_redraw();
+        self.window_redraws.extend(self.windows.keys());
+    }
+
+    /// The additional windows `handle_window_render` is called for in the
+    /// current render phase. Empty outside of it.
+    pub fn windows_to_redraw(&self) -> &[WindowId] {
+        &self.redrawing
+    }
+
+    /// Queues a one-off render to run after the state's `handle_render` on the
+    /// next frame, in the order they were queued. Requests a redraw, so the
+    /// next frame renders whatever the `RenderMode`.
+    pub fn enqueue_render<F: FnOnce(&Data<D, W>) + 'static>(&mut self, f: F) {
+        self.render_queue.push(Box::new(f));
+        self.request_redraw();
+    }
+
+    pub fn event_mode(&self) -> EventMode {
+        self.event_mode
+    }
+
+    pub fn set_event_mode(&mut self, mode: EventMode) {
+        self.event_mode = mode;
+    }
+
+    /// The rate the loop is currently ticking at, including any override and
+    /// the active state's `tick_rate`.
+    pub fn target_fps(&self) -> u32 {
+        self.state_rate.unwrap_or_else(|| self.configured_fps())
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        spf(self.target_fps())
+    }
+
+    fn configured_fps(&self) -> u32 {
+        match self.fps_override {
+            Some(FpsOverride { fps, until })
+                if until.is_none_or(|until| Instant::now() < until) =>
+            {
+                fps
+            }
+            _ => self.fps,
+        }
+    }
+
+    /// Adds a tick channel running at its own rate alongside the main tick,
+    /// dispatched to `handle_tick_channel` with the channel's name.
+    ///
+    /// # Panics
+    ///
+    /// If `rate` is zero.
+    pub fn add_tick_channel(&mut self, name: &'static str, rate: u32) {
+        assert!(rate > 0, "tick channel {:?} has a rate of zero", name);
+
+        self.remove_tick_channel(name);
+        self.tick_channels.push(TickChannel {
+            name,
+            spf: spf(rate),
+            accum: Duration::ZERO,
+        });
+    }
+
+    pub fn remove_tick_channel(&mut self, name: &'static str) {
+        self.tick_channels.retain(|channel| channel.name != name);
+    }
+
+    /// Time elapsed that hasn't been consumed by a tick yet.
+    pub fn accumulator(&self) -> Duration {
+        self.accum
+    }
+
+    /// Drops the tick backlog without running the ticks it was owed. The
+    /// simulation permanently falls behind wall-clock time by the cleared
+    /// amount, so runs that do this are no longer reproducible by timing.
+    pub fn clear_accumulator(&mut self) {
+        self.accum = Duration::ZERO;
+    }
+
+    /// Drops the tick backlog and restarts frame timing from now, so time
+    /// that passed outside the loop, e.g. between `App::step` calls, isn't
+    /// simulated.
+    pub fn reset_timing(&mut self) {
+        self.accum = Duration::ZERO;
+        self.prev_frame = Instant::now();
+
+        for channel in &mut self.tick_channels {
+            channel.accum = Duration::ZERO;
+        }
+    }
+
+    /// The simulation time covered by the current tick. This is one timestep
+    /// unless `CatchupStrategy::OneBig` merged several into a single tick.
+    pub fn tick_delta(&self) -> Duration {
+        self.tick_delta
+    }
+
+    /// Runs exactly one tick on the next frame while step debugging is
+    /// enabled through `RunConfig::enable_step_debugging`, e.g. from a debug
+    /// UI. Has no effect otherwise.
+    pub fn step_once(&mut self) {
+        self.step_requested = self.step_key.is_some();
+    }
+
+    /// The simulated time since the active state was entered, reset by every
+    /// transition. Only ticks that ran count, so time spent paused through
+    /// `RunConfig::should_tick` is excluded.
+    pub fn time_in_state(&self) -> Duration {
+        self.time_in_state
+    }
+
+    /// The window's inner size, kept up to date from resize events.
+    pub fn inner_size(&self) -> PhysicalSize<u32> {
+        self.inner_size
+    }
+
+    /// Whether the window has focus. This starts out `false` as winit has no
+    /// way to query it, and is updated once the platform reports a change.
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    pub fn ui_zoom(&self) -> f32 {
+        self.ui_zoom
+    }
+
+    pub fn set_ui_zoom(&mut self, zoom: f32) {
+        if zoom != self.ui_zoom {
+            self.ui_zoom = zoom;
+            self.content_scale_changed = true;
+        }
+    }
+
+    /// The window's scale factor multiplied by the user's UI zoom.
+    pub fn content_scale(&self) -> f64 {
+        self.scale_factor * self.ui_zoom as f64
+    }
+
+    fn set_scale_factor(&mut self, scale_factor: f64) {
+        if scale_factor != self.scale_factor {
+            self.scale_factor = scale_factor;
+            self.content_scale_changed = true;
+        }
+    }
+
+    fn take_content_scale_change(&mut self) -> Option<f64> {
+        if std::mem::take(&mut self.content_scale_changed) {
+            Some(self.content_scale())
+        } else {
+            None
+        }
+    }
+
+    /// The number of frames completed since the loop started.
+    pub fn frame_count(&self) -> u64 {
+        self.frame
+    }
+
+    /// The wall-clock time at which the current loop iteration started.
+    pub fn frame_instant(&self) -> Instant {
+        self.frame_start
+    }
+
+    /// Time spent so far in the current loop iteration.
+    pub fn frame_elapsed(&self) -> Duration {
+        self.frame_start.elapsed()
+    }
+
+    /// Ticks run per render over the last few renders, counting the ticks of
+    /// frames that skipped rendering towards the next render. 1.0 means one
+    /// tick per render, 0.5 means rendering twice per tick.
+    pub fn tick_render_ratio(&self) -> f32 {
+        if self.frame_ticks.is_empty() {
+            return 0.0;
+        }
+
+        self.frame_ticks.iter().sum::<u32>() as f32 / self.frame_ticks.len() as f32
+    }
+
+    /// Everything `App::start` does that doesn't involve the window, shared
+    /// with `HeadlessApp`.
+    pub(crate) fn start<T, S: State<D, W, T>>(
+        &mut self,
+        config: RunConfig<D, W>,
+        states: Vec<S>,
+    ) -> Stepper<D, W, S> {
+        let mut stack = states.clone();
+        let state = stack.pop().expect("the initial state stack is empty");
+
+        #[cfg(feature = "metrics")]
+        metrics::gauge!("stateloop_state", "state" => state.name()).set(1.0);
+
+        self.fps = config.fps;
+
+        self.tick_hashes.clear();
+        self.state_timings.clear();
+        self.time_in_state = Duration::ZERO;
+        self.quit_reason = None;
+        self.frame = 0;
+        self.dirty = true;
+        self.redraw_event = true;
+        self.event_mode = config.event_mode.unwrap_or(self.event_mode);
+        self.coalesce_resizes = config.render_mode == RenderMode::OnRequest;
+        self.step_key = config.step_key;
+        self.duplicate_states = config.duplicate_states;
+
+        state.on_enter(self);
+
+        if config.initial_resize {
+            let size = self.inner_size;
+            state.handle_resize(self, size, ResizeSource::Initial);
+        }
+
+        // Timing starts only once all setup is done, so initialisation time
+        // never shows up as a backlog of ticks on the first frame.
+        self.reset_timing();
+        self.state_rate = self.tick_rate_of(&state);
+
+        Stepper {
+            rate: self.state_rate,
+            config,
+            initial: states,
+            state,
+            stack,
+            alpha: 0.0,
+            finished: false,
+        }
+    }
+
+    pub(crate) fn snapshot<S: Clone>(&self, stepper: &Stepper<D, W, S>) -> LoopSnapshot<S> {
+        LoopSnapshot {
+            state: stepper.state.clone(),
+            stack: stepper.stack.clone(),
+            accum: self.accum,
+            frame: self.frame,
+            time_in_state: self.time_in_state,
+        }
+    }
+
+    pub(crate) fn restore<T, S: State<D, W, T>>(
+        &mut self,
+        stepper: &mut Stepper<D, W, S>,
+        snapshot: LoopSnapshot<S>,
+    ) {
+        stepper.state.on_exit(self);
+        snapshot.state.on_enter(self);
+        self.replace_state(&mut stepper.state, snapshot.state);
+        stepper.stack = snapshot.stack;
+
+        // Retimed first so the restored accumulator is measured against the
+        // restored state's tick rate.
+        stepper.rate = self.tick_rate_of(&stepper.state);
+        self.retime(stepper.rate);
+        self.reset_timing();
+
+        self.accum = snapshot.accum;
+        self.frame = snapshot.frame;
+        self.time_in_state = snapshot.time_in_state;
+        stepper.alpha = 0.0;
+    }
+
+    /// The rest of a frame once its events have been handled, as of `now`.
+    /// Returns `false` once the loop has quit.
+    pub(crate) fn finish_frame<T, S: State<D, W, T>>(
+        &mut self,
+        stepper: &mut Stepper<D, W, S>,
+        now: Instant,
+    ) -> bool {
+        // Measured from here rather than `frame_start`, which would also count
+        // the time spent blocked waiting for events.
+        let busy_start = Instant::now();
+
+        let Stepper {
+            config,
+            state,
+            initial,
+            stack,
+            rate,
+            alpha,
+            ..
+        } = stepper;
+
+        let frame = self.frame;
+
+        if let Some((size, source)) = self.pending_resize.take() {
+            state.handle_resize(self, size, source);
+        }
+
+        let mut rendered = false;
+
+        if config.phase_order == PhaseOrder::RenderThenTick {
+            rendered = self.render(config, state, *alpha);
+        }
+
+        self.expire_fps_override(now);
+        let next_rate = self.tick_rate_of(state);
+
+        if next_rate != *rate {
+            self.accum = Duration::ZERO;
+            *rate = next_rate;
+        }
+
+        let spf = self.retime(*rate);
+
+        let elapsed = if frame == 0 && config.skip_first_delta {
+            Duration::ZERO
+        } else {
+            now.saturating_duration_since(self.prev_frame)
+        };
+
+        self.accum += elapsed;
+        self.prev_frame = now;
+
+        if self.event_mode == EventMode::Wait || !config.ticks_enabled {
+            // Time spent blocked waiting for events isn't simulated.
+            self.accum = Duration::ZERO;
+        } else if self.step_key.is_some() {
+            self.accum = Duration::ZERO;
+
+            if std::mem::take(&mut self.step_requested) {
+                self.tick(config, state, spf);
+                self.dirty = true;
+            }
+        } else {
+            config.check_backlog(self.accum, spf);
+
+            self.catch_up(config, state, spf);
+            self.tick_channels(state, elapsed);
+        }
+
+        // How far the rendered frame is between the last tick and the next,
+        // as of the end of the most recent tick phase.
+        *alpha = if spf.is_zero() {
+            0.0
+        } else {
+            (self.accum.as_secs_f64() / spf.as_secs_f64()).clamp(0.0, 1.0)
+        };
+
+        self.phase(config, Phase::PostTick, PhaseEdge::Start);
+
+        if let Some(scale) = self.take_content_scale_change() {
+            state.handle_content_scale_changed(self, scale);
+        }
+
+        self.phase(config, Phase::PostTick, PhaseEdge::End);
+
+        if config.phase_order == PhaseOrder::TickThenRender {
+            rendered = self.render(config, state, *alpha);
+        }
+
+        let busy = busy_start.elapsed();
+        self.record_state_time(state.name(), busy);
+
+        config.run_post_frame(self);
+
+        self.window_closed_hooks(state);
+
+        if rendered {
+            config.complete_frame(frame);
+        }
+
+        if std::mem::take(&mut self.quit) {
+            return false;
+        }
+
+        if self.device_lost.take() {
+            match config.init_data(&self.window) {
+                Some(data) => {
+                    self.data = data;
+                    state.handle_device_lost(self);
+                }
+                None => {
+                    self.quit_reason = Some("device lost");
+                    return false;
+                }
+            }
+        }
+
+        if std::mem::take(&mut self.restart) {
+            // The hooks run either side of the rebuild, so the outgoing state
+            // can release what it keeps in the old data, and the initial one
+            // sets up the new.
+            state.on_exit(self);
+
+            if let Some(data) = config.init_data(&self.window) {
+                self.data = data;
+            }
+
+            *stack = initial.clone();
+            let top = stack.pop().expect("the initial state stack is empty");
+            top.on_enter(self);
+            self.replace_state(state, top);
+            self.reset_timing();
+            *alpha = 0.0;
+
+            return true;
+        }
+
+        if rendered {
+            self.record_frame_ticks();
+        }
+
+        self.frame += 1;
+
+        true
+    }
+
+    /// How long to wait at the end of a frame that took `busy`, to pace frames
+    /// to the render cap, or by default to the tick rate. A frame that ran
+    /// long waits for nothing, and `EventMode::Wait` blocks on events instead.
+    pub(crate) fn frame_pacing(&self, cap: RenderCap, busy: Duration) -> Duration {
+        if self.event_mode != EventMode::Poll {
+            return Duration::ZERO;
+        }
+
+        match cap {
+            RenderCap::TickRate => self.spf.saturating_sub(self.accum),
+            RenderCap::Fps(cap) => spf(cap).saturating_sub(busy),
+            RenderCap::Uncapped => Duration::ZERO,
+        }
+    }
+
+    /// Calls `on_shutdown` on the state a finished run ended in, returning it.
+    pub(crate) fn shut_down<T, S: State<D, W, T>>(&mut self, stepper: Stepper<D, W, S>) -> S {
+        // The loop may have quit before the end of the frame that closed them.
+        self.window_closed_hooks(&stepper.state);
+
+        if let Some(reason) = self.quit_reason {
+            eprintln!("stateloop: quit: {}", reason);
+        }
+
+        stepper.state.on_shutdown(self);
+        stepper.state
+    }
+
+    fn window_closed_hooks<T, S: State<D, W, T>>(&mut self, state: &S) {
+        for window in std::mem::take(&mut self.closed_windows) {
+            state.handle_window_closed(self, window);
+        }
+    }
+
+    pub(crate) fn render<T, S: State<D, W, T>>(
+        &mut self,
+        config: &mut RunConfig<D, W>,
+        state: &S,
+        alpha: f64,
+    ) -> bool {
+        (self.apply_ops)(self);
+
+        if !self.rendering_enabled {
+            return false;
+        }
+
+        let requested = self.redraw_requested.take();
+        let dirty = std::mem::take(&mut self.dirty) || requested;
+        let redraw = std::mem::take(&mut self.redraw_event) || requested;
+
+        let main = match config.render_mode {
+            RenderMode::Continuous => true,
+            RenderMode::OnChange => dirty,
+            RenderMode::OnRequest => redraw,
+        };
+
+        // Each additional window is skipped on its own when it has nothing
+        // new to show, so an idle window isn't redrawn alongside a busy one.
+        let windows = std::mem::take(&mut self.window_redraws);
+
+        match config.render_mode {
+            RenderMode::Continuous => self.redrawing.extend(self.windows.keys()),
+            _ => self.redrawing.extend(
+                windows
+                    .into_iter()
+                    .filter(|id| self.windows.contains_key(id)),
+            ),
+        }
+
+        if !main && self.redrawing.is_empty() {
+            return false;
+        }
+
+        self.phase(config, Phase::Render, PhaseEdge::Start);
+
+        if main {
+            let mut retries = config.render_retries;
+
+            loop {
+                state.handle_render(self, alpha);
+
+                if !self.render_failed.take() {
+                    break;
+                }
+
+                if retries == 0 {
+                    self.quit = true;
+                    self.quit_reason = Some("render failed");
+                    break;
+                }
+
+                retries -= 1;
+            }
+        }
+
+        for &window in &self.redrawing {
+            state.handle_window_render(self, window, alpha);
+        }
+
+        self.redrawing.clear();
+
+        if main {
+            for f in std::mem::take(&mut self.render_queue) {
+                f(self);
+            }
+
+            self.record_input_latency();
+        }
+
+        self.phase(config, Phase::Render, PhaseEdge::End);
+        main
+    }
+
+    pub(crate) fn phase(&mut self, config: &mut RunConfig<D, W>, phase: Phase, edge: PhaseEdge) {
+        self.phase = match edge {
+            PhaseEdge::Start => Some(phase),
+            PhaseEdge::End => None,
+        };
+
+        config.profile(phase, edge, self.frame);
+    }
+
+    pub(crate) fn tick<T, S: State<D, W, T>>(
+        &mut self,
+        config: &mut RunConfig<D, W>,
+        state: &S,
+        delta: Duration,
+    ) {
+        if !config.tick_allowed(self) {
+            return;
+        }
+
+        self.phase(config, Phase::Tick, PhaseEdge::Start);
+        self.run_tick(state, delta);
+        self.phase(config, Phase::Tick, PhaseEdge::End);
+
+        config.check_determinism(&self.data, &mut self.tick_hashes);
+    }
+
+    pub(crate) fn catch_up<T, S: State<D, W, T>>(
+        &mut self,
+        config: &mut RunConfig<D, W>,
+        state: &S,
+        spf: Duration,
+    ) {
+        let ticks = self
+            .accum
+            .as_nanos()
+            .checked_div(spf.as_nanos())
+            .unwrap_or(0) as u32;
+
+        match config.catchup_strategy {
+            CatchupStrategy::ManySmall => {
+                while self.accum >= spf {
+                    self.accum -= spf;
+                    self.tick(config, state, spf);
+                }
+            }
+            CatchupStrategy::OneBig if ticks > 0 => {
+                self.accum -= spf * ticks;
+                self.tick(config, state, spf * ticks);
+            }
+            CatchupStrategy::Clamp if ticks > 0 => {
+                self.accum -= spf * ticks;
+                self.tick(config, state, spf);
+            }
+            _ => (),
+        }
+    }
+
+    pub(crate) fn tick_channels<T, S: State<D, W, T>>(&mut self, state: &S, elapsed: Duration) {
+        for channel in &mut self.tick_channels {
+            channel.accum += elapsed;
+        }
+
+        let mut i = 0;
+        self.phase = Some(Phase::Tick);
+
+        while let Some(channel) = self.tick_channels.get_mut(i) {
+            if channel.accum >= channel.spf {
+                channel.accum -= channel.spf;
+
+                let name = channel.name;
+                state.handle_tick_channel(self, name);
+            } else {
+                i += 1;
+            }
+        }
+
+        self.phase = None;
+    }
+
+    pub(crate) fn dispatch_user_event<T, S: State<D, W, T>>(
+        &mut self,
+        state: &mut S,
+        stack: &mut Vec<S>,
+        event: T,
+    ) {
+        self.dirty = true;
+
+        let action = state.handle_user_event(self, event);
+        self.apply(state, stack, action);
+    }
+
+    pub(crate) fn dispatch_event<T, S: State<D, W, T>>(
+        &mut self,
+        state: &mut S,
+        stack: &mut Vec<S>,
+        window: WindowId,
+        event: Event,
+    ) {
+        // Only the main window's events feed the size, scale and focus
+        // tracking, and its hooks.
+        let main = self.window_id.is_none_or(|id| id == window);
+
+        if main {
+            self.dirty = true;
+        } else {
+            self.window_redraws.insert(window);
+        }
+
+        let resize = match event {
+            _ if !main => None,
+            Event::Resized(size) => {
+                self.inner_size = size;
+
+                let source = if self.requested_size.take() == Some(size) {
+                    ResizeSource::Programmatic
+                } else {
+                    ResizeSource::User
+                };
+
+                Some((size, source))
+            }
+            Event::ScaleFactorChanged {
+                scale_factor,
+                ref new_inner_size,
+            } => {
+                self.inner_size = **new_inner_size;
+                self.set_scale_factor(scale_factor);
+                None
+            }
+            _ => None,
+        };
+
+        let focus_changed = match event {
+            _ if !main => false,
+            Event::Focused(focused) if focused != self.focused => {
+                self.focused = focused;
+                self.keys.clear();
+                true
+            }
+            _ => false,
+        };
+
+        let key = match event {
+            Event::KeyboardInput { ref input, .. } => self.keys.process(input),
+            _ => None,
+        };
+
+        if let Some(key) = key {
+            if key.pressed && !key.repeat && self.step_key == Some(key.key) {
+                self.step_once();
+            }
+        }
+
+        let mapped = match event {
+            Event::MouseInput {
+                state: button_state,
+                button,
+                ..
+            } => self.input_map.action(button).map(|action| ActionEvent {
+                action,
+                pressed: button_state == ElementState::Pressed,
+            }),
+            _ => key.filter(|key| !key.repeat).and_then(|key| {
+                self.input_map.action(key.key).map(|action| ActionEvent {
+                    action,
+                    pressed: key.pressed,
+                })
+            }),
+        };
+
+        let action = match event {
+            Event::CloseRequested => state.handle_close_requested(self, window),
+            event => {
+                let mut action = state.handle_event(self, window, event);
+
+                if let (Action::Continue, Some(key)) = (&action, key) {
+                    action = state.handle_key(self, key);
+                }
+
+                if let (Action::Continue, Some(mapped)) = (&action, mapped) {
+                    action = state.handle_action(self, mapped);
+                }
+
+                action
+            }
+        };
+
+        self.apply(state, stack, action);
+
+        if let Some((size, source)) = resize {
+            if self.coalesce_resizes {
+                // Some platforms report every step of a drag, each followed
+                // by a redraw request. Only the latest size is handled, once
+                // per frame just before the one render the requests cause.
+                self.pending_resize = Some((size, source));
+            } else {
+                state.handle_resize(self, size, source);
+            }
+        }
+
+        if focus_changed {
+            state.handle_focus_changed(self, self.focused);
+        }
+
+        if let Some(scale) = self.take_content_scale_change() {
+            state.handle_content_scale_changed(self, scale);
+        }
+    }
+
+    pub(crate) fn tick_rate_of<T, S: State<D, W, T>>(&self, state: &S) -> Option<u32> {
+        let rate = state.tick_rate(self);
+        assert!(rate != Some(0), "{} has a tick rate of zero", state.name());
+        rate
+    }
+
+    /// The timestep for a tick rate, or for the loop's rate if `None`. When
+    /// it differs from the last one, the partial timestep accumulated so far
+    /// is rescaled to the new one, so raising the rate can't turn leftover
+    /// time into a burst of ticks.
+    pub(crate) fn retime(&mut self, rate: Option<u32>) -> Duration {
+        self.state_rate = rate;
+        let spf = self.tick_interval();
+
+        if !self.spf.is_zero() && spf != self.spf {
+            self.accum = self
+                .accum
+                .mul_f64(spf.as_secs_f64() / self.spf.as_secs_f64());
+        }
+
+        self.spf = spf;
+        spf
+    }
+
+    pub(crate) fn take_quit(&mut self) -> bool {
+        std::mem::take(&mut self.quit)
+    }
+
+    pub(crate) fn run_tick<T, S: State<D, W, T>>(&mut self, state: &S, delta: Duration) {
+        self.tick_delta = delta;
+        self.ticks_this_frame += 1;
+        self.time_in_state += delta;
+
+        state.handle_tick(self, delta);
+
+        let mut commands = Commands::new();
+        state.handle_tick_commands(self, &mut commands);
+        commands.flush(self);
+    }
+
+    fn apply<T, S: State<D, W, T>>(
+        &mut self,
+        state: &mut S,
+        stack: &mut Vec<S>,
+        action: Action<S>,
+    ) {
+        match action {
+            Action::Continue => (),
+            Action::Done(next) => {
+                self.transition(state, next);
+            }
+            Action::Push(next) => {
+                if let Some(on_duplicate) = self.duplicate_states {
+                    let variant = std::mem::discriminant(&next);
+                    let duplicate = std::iter::once(&*state)
+                        .chain(stack.iter())
+                        .any(|s| std::mem::discriminant(s) == variant);
+
+                    if duplicate {
+                        let message = format!("{} pushed while already on the stack", next.name());
+
+                        match on_duplicate {
+                            DuplicateStates::Warn => eprintln!("stateloop: {}", message),
+                            DuplicateStates::Panic => panic!("{}", message),
+                        }
+                    }
+                }
+
+                let prev = self.transition(state, next);
+                stack.push(prev);
+            }
+            Action::Pop => match stack.pop() {
+                Some(prev) => {
+                    self.transition(state, prev);
+                }
+                None => self.quit = true,
+            },
+            Action::Quit => self.quit = true,
+            Action::QuitBecause(reason) => {
+                self.quit = true;
+                self.quit_reason = Some(reason);
+            }
+        }
+    }
+
+    /// Replaces the active state, returning the one that was left. The exit
+    /// and enter hooks run when the variant changes.
+    fn transition<T, S: State<D, W, T>>(&mut self, state: &mut S, to: S) -> S {
+        if std::mem::discriminant(&*state) != std::mem::discriminant(&to) {
+            state.on_exit(self);
+            to.on_enter(self);
+        }
+
+        self.replace_state(state, to)
+    }
+
+    /// Replaces the active state without running any hooks.
+    fn replace_state<T, S: State<D, W, T>>(&mut self, state: &mut S, to: S) -> S {
+        self.dirty = true;
+        self.window_redraws.extend(self.windows.keys());
+        self.time_in_state = Duration::ZERO;
+
+        #[cfg(feature = "metrics")]
+        {
+            metrics::gauge!("stateloop_state", "state" => state.name()).set(0.0);
+            metrics::gauge!("stateloop_state", "state" => to.name()).set(1.0);
+            metrics::counter!("stateloop_transitions").increment(1);
+        }
+
+        std::mem::replace(state, to)
+    }
+
+    /// The average time between input arriving and the end of the next
+    /// render, over the last few frames that had input.
+    pub fn input_latency(&self) -> Option<Duration> {
+        if self.input_latencies.is_empty() {
+            return None;
+        }
+
+        Some(self.input_latencies.iter().sum::<Duration>() / self.input_latencies.len() as u32)
+    }
+
+    /// The time spent handling events, ticking and rendering in each state
+    /// this run, keyed by `State::name` and slowest first. Time spent waiting
+    /// for events isn't counted.
+    pub fn state_timings(&self) -> Vec<(&'static str, Duration)> {
+        let mut timings = self.state_timings.clone();
+        timings.sort_by_key(|&(_, time)| std::cmp::Reverse(time));
+        timings
+    }
+
+    fn stamp_input(&mut self, event: &Event) {
+        let input = matches!(
+            event,
+            Event::KeyboardInput { .. }
+                | Event::ReceivedCharacter(_)
+                | Event::CursorMoved { .. }
+                | Event::MouseInput { .. }
+                | Event::MouseWheel { .. }
+                | Event::Touch(_)
+        );
+
+        if input && self.pending_input.is_none() {
+            self.pending_input = Some(Instant::now());
+        }
+    }
+
+    fn record_input_latency(&mut self) {
+        if let Some(arrived) = self.pending_input.take() {
+            if self.input_latencies.len() == STATS_FRAMES {
+                self.input_latencies.pop_front();
+            }
+
+            self.input_latencies.push_back(arrived.elapsed());
+        }
+    }
+
+    pub(crate) fn record_state_time(&mut self, name: &'static str, time: Duration) {
+        match self.state_timings.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, total)) => *total += time,
+            None => self.state_timings.push((name, time)),
+        }
+    }
+
+    fn record_frame_ticks(&mut self) {
+        if self.frame_ticks.len() == STATS_FRAMES {
+            self.frame_ticks.pop_front();
+        }
+
+        self.frame_ticks
+            .push_back(std::mem::take(&mut self.ticks_this_frame));
+    }
+
+    fn expire_fps_override(&mut self, now: Instant) {
+        if let Some(FpsOverride {
+            until: Some(until), ..
+        }) = self.fps_override
+        {
+            if now >= until {
+                self.fps_override = None;
+            }
+        }
+    }
+}
+
+impl<D, W: AsWindow> Data<D, W> {
+    /// The id of the main window, to tell its events apart from those of
+    /// windows opened with `open_window`.
+    pub fn window_id(&self) -> WindowId {
+        self.window.as_window().id()
+    }
+
+    /// Resizes the window. The `Resized` event this causes is reported to
+    /// `handle_resize` as `ResizeSource::Programmatic`.
+    pub fn set_inner_size<S: Into<Size>>(&mut self, size: S) {
+        let size = size.into();
+        let physical = size.to_physical(self.scale_factor);
+
+        // Setting the current size doesn't produce a `Resized` event to match.
+        self.requested_size = Some(physical).filter(|&size| size != self.inner_size);
+        self.window.as_window().set_inner_size(size);
+    }
+
+    pub fn set_min_inner_size<S: Into<Size>>(&self, size: Option<S>) {
+        self.window.as_window().set_min_inner_size(size);
+    }
+
+    /// The refresh rate of the monitor the window is currently on, taken from
+    /// its fastest video mode at the monitor's current resolution.
+    pub fn refresh_rate(&self) -> Option<u32> {
+        let monitor = self.window.as_window().current_monitor()?;
+        let size = monitor.size();
+
+        monitor
+            .video_modes()
+            .filter(|mode| mode.size() == size)
+            .map(|mode| mode.refresh_rate() as u32)
+            .max()
+    }
+
+    /// The video modes supported by the monitor the window is on. If the
+    /// window spans monitors, the one it overlaps the most is used.
+    pub fn current_monitor_video_modes(&self) -> Vec<VideoMode> {
+        self.overlapping_monitor()
+            .map(|monitor| monitor.video_modes().collect())
+            .unwrap_or_default()
+    }
+
+    /// Switches to exclusive fullscreen in the given mode, typically one from
+    /// `current_monitor_video_modes`.
+    pub fn set_exclusive_fullscreen(&self, mode: VideoMode) {
+        self.window
+            .as_window()
+            .set_fullscreen(Some(Fullscreen::Exclusive(mode)));
+    }
+
+    /// Sets the cursor icon, skipping the platform call if it is already
+    /// showing, as some platforms flicker on repeated sets.
+    pub fn set_cursor_icon(&self, icon: CursorIcon) {
+        if self.cursor_icon.replace(icon) != icon {
+            self.window.as_window().set_cursor_icon(icon);
+        }
+    }
+
+    fn overlapping_monitor(&self) -> Option<MonitorHandle> {
+        let window = self.window.as_window();
+
+        let (position, size) = match window.outer_position() {
+            Ok(position) => (position, window.outer_size()),
+            Err(_) => return window.current_monitor(),
+        };
+
+        let overlap = |monitor: &MonitorHandle| {
+            let (mpos, msize) = (monitor.position(), monitor.size());
+
+            let width = (position.x + size.width as i32).min(mpos.x + msize.width as i32)
+                - position.x.max(mpos.x);
+            let height = (position.y + size.height as i32).min(mpos.y + msize.height as i32)
+                - position.y.max(mpos.y);
+
+            width.max(0) as i64 * height.max(0) as i64
+        };
+
+        window
+            .available_monitors()
+            .map(|monitor| (overlap(&monitor), monitor))
+            .filter(|&(area, _)| area > 0)
+            .max_by_key(|&(area, _)| area)
+            .map(|(_, monitor)| monitor)
+            .or_else(|| window.current_monitor())
+    }
+
+    fn apply_window_ops(&mut self) {
+        let ops = std::mem::take(&mut self.window_ops);
+
+        if let Some(size) = ops.inner_size {
+            self.set_inner_size(size);
+        }
+
+        let window = self.window.as_window();
+
+        if let Some(title) = ops.title {
+            window.set_title(&title);
+        }
+
+        if let Some(size) = ops.min_inner_size {
+            window.set_min_inner_size(size);
+        }
+
+        if let Some(icon) = ops.cursor_icon {
+            self.set_cursor_icon(icon);
+        }
+    }
+}
+
+#[cfg(feature = "threaded")]
+mod threaded {
+    use winit::event_loop::EventLoop;
+
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    pub(super) fn new_any_thread() -> Option<fn() -> EventLoop<()>> {
+        use winit::platform::unix::EventLoopExtUnix;
+        Some(EventLoop::new_any_thread)
+    }
+
+    #[cfg(target_os = "windows")]
+    pub(super) fn new_any_thread() -> Option<fn() -> EventLoop<()>> {
+        use winit::platform::windows::EventLoopExtWindows;
+        Some(EventLoop::new_any_thread)
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "windows"
+    )))]
+    pub(super) fn new_any_thread() -> Option<fn() -> EventLoop<()>> {
+        None
+    }
+}
+
+// winit panics rather than failing when there is no display to connect to,
+// e.g. on a headless CI machine.
+fn create_event_loop<T, E1, E2>(f: fn() -> EventLoop<T>) -> Result<EventLoop<T>, AppError<E1, E2>> {
+    std::panic::catch_unwind(f).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+
+        AppError::EventLoopError(message)
+    })
+}
+
+// Exact to the nanosecond and never zero, as a zero timestep would make
+// catching up loop forever. Rates are checked to be non-zero where they're set.
+fn spf(fps: u32) -> Duration {
+    Duration::from_secs_f64(1.0 / fps as f64).max(Duration::from_nanos(1))
 }
@@ -0,0 +1,45 @@
+//////////////////////////////////////////////////////////////////////////////
+//  File: stateloop/commands.rs
+//////////////////////////////////////////////////////////////////////////////
+//  Copyright 2017 Samuel Sleight
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+//////////////////////////////////////////////////////////////////////////////
+
+type CommandFn<T> = Box<dyn FnOnce(&mut T)>;
+
+/// Deferred mutations queued during a tick and applied, in order, once the
+/// tick has finished.
+pub struct Commands<T: ?Sized> {
+    queue: Vec<CommandFn<T>>,
+}
+
+impl<T: ?Sized> Commands<T> {
+    pub(crate) fn new() -> Commands<T> {
+        Commands { queue: Vec::new() }
+    }
+
+    pub fn push<F: FnOnce(&mut T) + 'static>(&mut self, f: F) {
+        self.queue.push(Box::new(f));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub(crate) fn flush(self, target: &mut T) {
+        for f in self.queue {
+            f(target);
+        }
+    }
+}
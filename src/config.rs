@@ -0,0 +1,417 @@
+//////////////////////////////////////////////////////////////////////////////
+//  File: stateloop/config.rs
+//////////////////////////////////////////////////////////////////////////////
+//  Copyright 2017 Samuel Sleight
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+//////////////////////////////////////////////////////////////////////////////
+
+use std::time::Duration;
+
+use crate::app::Data;
+use crate::input::Key;
+
+pub(crate) type BacklogFn = Box<dyn FnMut(Duration)>;
+type HashFn<D> = Box<dyn FnMut(&D) -> u64>;
+type DataInitFn<D, W> = Box<dyn FnMut(&W) -> D>;
+type PredicateFn<D, W> = Box<dyn FnMut(&Data<D, W>) -> bool>;
+pub(crate) type ProfilerFn = Box<dyn FnMut(Phase, PhaseEdge, u64)>;
+type FrameFn<D, W> = Box<dyn FnMut(&mut Data<D, W>)>;
+type FrameCompleteFn = Box<dyn FnMut(u64)>;
+
+/// How the loop catches up when more than one timestep has elapsed since the
+/// last frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CatchupStrategy {
+    /// Run one tick per elapsed timestep. The simulation sees exactly the
+    /// same steps regardless of frame timing, but a long stall is followed
+    /// by a burst of ticks which can itself stall the next frame.
+    ManySmall,
+
+    /// Run a single tick covering every elapsed timestep, reported through
+    /// `Data::tick_delta`. Catching up is cheap, but the larger step can
+    /// destabilise physics and makes the simulation frame-rate dependent.
+    OneBig,
+
+    /// Run a single regular tick and drop the rest of the backlog. The
+    /// simulation stays stable but falls behind wall-clock time.
+    Clamp,
+}
+
+/// How the loop waits between frames.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EventMode {
+    /// Handle whatever events are pending, then sleep until the next frame
+    /// is due.
+    Poll,
+
+    /// Block until the next window event arrives. Ticks don't run in this
+    /// mode, and the time spent blocked is never simulated, so it suits apps
+    /// that are idle most of the time.
+    Wait,
+
+    /// Block until the next window event arrives, the next tick is due or
+    /// the given interval has passed since the frame started, whichever
+    /// comes first. Ticks run as in `Poll`, so the interval only matters when
+    /// it is shorter than the timestep or ticks are disabled, e.g. for a
+    /// clock that must update once a second.
+    WaitUntil(Duration),
+}
+
+/// When the loop calls `handle_render`.
+///
+/// Windows opened with `Data::open_window` are rendered every frame under
+/// `Continuous`. Under the other modes each one renders only when it has
+/// something new to show: an event for it, a state transition, or a call to
+/// `Data::request_window_redraw` or `Data::request_redraw_all`. This happens
+/// whether or not the main window renders.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Render every frame.
+    Continuous,
+
+    /// Render only on frames where something may have changed: the first
+    /// frame, a frame that handled a main window event or a state
+    /// transition, one where `Data::mark_dirty` was called, or one where the
+    /// platform asked for a redraw (e.g. after the window was uncovered).
+    OnChange,
+
+    /// Render only when the platform asks for a redraw, e.g. after the window
+    /// was uncovered or resized, or after `Data::request_redraw`. Suits
+    /// mostly static UIs. The first frame is always rendered.
+    ///
+    /// Resizes are coalesced in this mode: however many `Resized` events and
+    /// redraw requests a live resize produces within a frame, `handle_resize`
+    /// is called once with the latest size and the frame renders once.
+    OnRequest,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum RenderCap {
+    TickRate,
+    Fps(u32),
+    Uncapped,
+}
+
+/// The order of the render and tick phases within a frame. Events are always
+/// processed first.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PhaseOrder {
+    /// Render, then run the frame's ticks. The rendered frame shows the state
+    /// before this frame's ticks, adding up to a timestep of latency.
+    RenderThenTick,
+
+    /// Run the frame's ticks, then render. The rendered frame reflects the
+    /// latest tick, but time spent ticking delays the frame being shown.
+    TickThenRender,
+}
+
+/// What `RunConfig::forbid_duplicate_stack_states` does when a state is
+/// pushed while a state of the same variant is already on the stack.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DuplicateStates {
+    /// Print a warning to stderr and push the state anyway.
+    Warn,
+
+    /// Panic.
+    Panic,
+}
+
+/// The parts of a loop iteration reported to `RunConfig::profiler`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Phase {
+    Events,
+    Tick,
+    PostTick,
+    Render,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PhaseEdge {
+    Start,
+    End,
+}
+
+pub struct RunConfig<D, W> {
+    pub(crate) fps: u32,
+    pub(crate) catchup_strategy: CatchupStrategy,
+    pub(crate) phase_order: PhaseOrder,
+    pub(crate) render_mode: RenderMode,
+    pub(crate) render_cap: RenderCap,
+    pub(crate) skip_first_delta: bool,
+    pub(crate) max_event_time: Option<Duration>,
+    pub(crate) ticks_enabled: bool,
+    pub(crate) initial_resize: bool,
+    pub(crate) render_retries: u32,
+    pub(crate) event_mode: Option<EventMode>,
+    pub(crate) step_key: Option<Key>,
+    pub(crate) duplicate_states: Option<DuplicateStates>,
+
+    determinism_hash: Option<HashFn<D>>,
+    determinism_reference: Option<Vec<u64>>,
+
+    should_tick: Option<PredicateFn<D, W>>,
+
+    pub(crate) profiler: Option<ProfilerFn>,
+    pub(crate) falling_behind: Option<BacklogFn>,
+
+    data_init: Option<DataInitFn<D, W>>,
+    post_frame: Option<FrameFn<D, W>>,
+    frame_complete: Option<FrameCompleteFn>,
+}
+
+impl<D, W> RunConfig<D, W> {
+    /// # Panics
+    ///
+    /// If `fps` is zero.
+    pub fn new(fps: u32) -> Self {
+        assert!(fps > 0, "the frame rate must be non-zero");
+
+        RunConfig {
+            fps,
+            catchup_strategy: CatchupStrategy::ManySmall,
+            phase_order: PhaseOrder::RenderThenTick,
+            render_mode: RenderMode::Continuous,
+            render_cap: RenderCap::TickRate,
+            skip_first_delta: false,
+            max_event_time: None,
+            ticks_enabled: true,
+            initial_resize: true,
+            render_retries: 1,
+            event_mode: None,
+            step_key: None,
+            duplicate_states: None,
+
+            determinism_hash: None,
+            determinism_reference: None,
+
+            should_tick: None,
+
+            profiler: None,
+            falling_behind: None,
+
+            data_init: None,
+            post_frame: None,
+            frame_complete: None,
+        }
+    }
+
+    pub fn catchup_strategy(mut self, strategy: CatchupStrategy) -> Self {
+        self.catchup_strategy = strategy;
+        self
+    }
+
+    pub fn phase_order(mut self, order: PhaseOrder) -> Self {
+        self.phase_order = order;
+        self
+    }
+
+    /// The event mode the loop starts in. It can still be changed while
+    /// running through `Data::set_event_mode`. If unset, the app's current
+    /// mode is kept, which is `EventMode::Poll` unless changed.
+    pub fn event_mode(mut self, mode: EventMode) -> Self {
+        self.event_mode = Some(mode);
+        self
+    }
+
+    pub fn render_mode(mut self, mode: RenderMode) -> Self {
+        self.render_mode = mode;
+        self
+    }
+
+    /// Limits how often frames, and so renders, run, separately from the
+    /// tick rate given to `new`. `None` leaves rendering uncapped, e.g. to
+    /// rely on vsync. By default frames are paced to the tick rate.
+    ///
+    /// # Panics
+    ///
+    /// If `cap` is `Some(0)`.
+    pub fn render_cap(mut self, cap: Option<u32>) -> Self {
+        assert!(cap != Some(0), "the render cap must be non-zero");
+        self.render_cap = cap.map_or(RenderCap::Uncapped, RenderCap::Fps);
+        self
+    }
+
+    /// Discards the time taken by the first frame, so that a slow first
+    /// event pass or render doesn't cause a burst of ticks.
+    pub fn skip_first_delta(mut self, skip: bool) -> Self {
+        self.skip_first_delta = skip;
+        self
+    }
+
+    /// Stops handling events once this much time has been spent on them in a
+    /// frame. The remaining events are handled, in order, at the start of the
+    /// next frame.
+    pub fn max_event_time(mut self, budget: Duration) -> Self {
+        self.max_event_time = Some(budget);
+        self
+    }
+
+    /// How many times a render reported as failed through
+    /// `Data::report_render_failed` is retried within the frame before the
+    /// loop gives up and quits. Defaults to 1.
+    pub fn render_retries(mut self, retries: u32) -> Self {
+        self.render_retries = retries;
+        self
+    }
+
+    /// Calls `handle_resize` with the window's size and
+    /// `ResizeSource::Initial` before the first frame, so size-dependent
+    /// state such as a viewport can be set up there rather than starting
+    /// out zero-sized. On by default.
+    pub fn initial_resize(mut self, enabled: bool) -> Self {
+        self.initial_resize = enabled;
+        self
+    }
+
+    /// Never ticks: only events and rendering run, e.g. for viewer tools that
+    /// share states with a game but never simulate. Unlike `should_tick`, no
+    /// time is accumulated and tick channels don't run either.
+    pub fn disable_ticks(mut self) -> Self {
+        self.ticks_enabled = false;
+        self
+    }
+
+    /// Freezes the simulation for debugging: `handle_tick` only runs once
+    /// per press of `key` or call to `Data::step_once`, and the frame renders
+    /// after each step. Tick channels don't run at all. Events and rendering
+    /// carry on as normal, and the key still reaches the state.
+    pub fn enable_step_debugging(mut self, key: Key) -> Self {
+        self.step_key = Some(key);
+        self
+    }
+
+    /// Checks every `Action::Push` against the active state and the states
+    /// below it, catching e.g. a pause menu pushed over another pause menu.
+    /// States are compared by variant only, so a `State` that isn't an enum
+    /// always counts as a duplicate. Off by default.
+    pub fn forbid_duplicate_stack_states(mut self, on_duplicate: DuplicateStates) -> Self {
+        self.duplicate_states = Some(on_duplicate);
+        self
+    }
+
+    /// Evaluated before every tick. When it returns `false` the tick's time
+    /// is still consumed, so there is no burst later, but `handle_tick` isn't
+    /// called, e.g. to let time pass without simulating.
+    pub fn should_tick<F: FnMut(&Data<D, W>) -> bool + 'static>(mut self, f: F) -> Self {
+        self.should_tick = Some(Box::new(f));
+        self
+    }
+
+    /// Hashes the user data after every tick. The hashes of a run are
+    /// available from `App::tick_hashes` once it returns.
+    pub fn determinism_check<F: FnMut(&D) -> u64 + 'static>(mut self, hash: F) -> Self {
+        self.determinism_hash = Some(Box::new(hash));
+        self
+    }
+
+    /// Hashes from a previous run to compare against. The loop panics on the
+    /// first tick whose hash differs from the reference.
+    pub fn determinism_reference(mut self, reference: Vec<u64>) -> Self {
+        self.determinism_reference = Some(reference);
+        self
+    }
+
+    /// Called at the start and end of every phase of every frame with the
+    /// current frame number, e.g. to forward to an external profiler.
+    pub fn profiler<F: FnMut(Phase, PhaseEdge, u64) + 'static>(mut self, f: F) -> Self {
+        self.profiler = Some(Box::new(f));
+        self
+    }
+
+    /// Called with the tick backlog whenever a frame starts its ticks more
+    /// than two timesteps behind, i.e. the simulation can't keep up.
+    pub fn on_falling_behind<F: FnMut(Duration) + 'static>(mut self, f: F) -> Self {
+        self.falling_behind = Some(Box::new(f));
+        self
+    }
+
+    pub(crate) fn tick_allowed(&mut self, data: &Data<D, W>) -> bool {
+        self.should_tick.as_mut().is_none_or(|f| f(data))
+    }
+
+    pub(crate) fn check_backlog(&mut self, backlog: Duration, spf: Duration) {
+        if let Some(f) = self.falling_behind.as_mut() {
+            if backlog > spf * 2 {
+                f(backlog);
+            }
+        }
+    }
+
+    /// Rebuilds the user data after the renderer reports the device as lost
+    /// through `Data::report_device_lost`, or when a state calls
+    /// `Data::request_restart`. Without this, device loss quits the loop and
+    /// a restart keeps the existing user data.
+    pub fn data_init<F: FnMut(&W) -> D + 'static>(mut self, f: F) -> Self {
+        self.data_init = Some(Box::new(f));
+        self
+    }
+
+    /// Called at the end of every frame, after rendering, whatever the
+    /// state, for app-wide work such as pumping an audio mixer.
+    pub fn post_frame<F: FnMut(&mut Data<D, W>) + 'static>(mut self, f: F) -> Self {
+        self.post_frame = Some(Box::new(f));
+        self
+    }
+
+    /// Called with the frame number at the end of every frame that rendered,
+    /// after `post_frame`, for signalling other components, e.g. through an
+    /// `EventLoopProxy` or a channel to a harness waiting to capture.
+    pub fn on_frame_complete<F: FnMut(u64) + 'static>(mut self, f: F) -> Self {
+        self.frame_complete = Some(Box::new(f));
+        self
+    }
+
+    pub(crate) fn init_data(&mut self, window: &W) -> Option<D> {
+        self.data_init.as_mut().map(|f| f(window))
+    }
+
+    pub(crate) fn run_post_frame(&mut self, data: &mut Data<D, W>) {
+        if let Some(f) = self.post_frame.as_mut() {
+            f(data);
+        }
+    }
+
+    pub(crate) fn complete_frame(&mut self, frame: u64) {
+        if let Some(f) = self.frame_complete.as_mut() {
+            f(frame);
+        }
+    }
+
+    pub(crate) fn profile(&mut self, phase: Phase, edge: PhaseEdge, frame: u64) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler(phase, edge, frame);
+        }
+    }
+
+    pub(crate) fn check_determinism(&mut self, data: &D, hashes: &mut Vec<u64>) {
+        if let Some(hash) = self.determinism_hash.as_mut() {
+            let tick = hashes.len();
+            let hash = hash(data);
+
+            if let Some(&expected) = self
+                .determinism_reference
+                .as_ref()
+                .and_then(|reference| reference.get(tick))
+            {
+                assert_eq!(
+                    hash, expected,
+                    "tick {} diverged from the determinism reference",
+                    tick
+                );
+            }
+
+            hashes.push(hash);
+        }
+    }
+}
@@ -1,11 +1,21 @@
-use std::fmt::Debug;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
 
 #[derive(Debug)]
 pub enum NoError {}
 
+impl Display for NoError {
+    fn fmt(&self, _: &mut Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+impl Error for NoError {}
+
 pub trait MaybeResult<T> {
     type Error: Debug;
 
+    #[allow(clippy::wrong_self_convention)]
     fn as_result(self) -> Result<T, Self::Error>;
 }
 
@@ -38,6 +48,67 @@ where
 
 #[derive(Debug)]
 pub enum AppError<E1, E2> {
+    /// The event loop couldn't be created, usually because there is no
+    /// display. Holds the platform's message.
+    EventLoopError(String),
+
+    /// A tick rate of zero was given to `AppBuilder::tick_rate`.
+    InvalidTickRate,
+
+    /// The event loop can't run off the main thread on this platform, so
+    /// `App::run_on_thread` isn't available.
+    UnsupportedPlatform,
+
     WindowError(E1),
     DataError(E2),
 }
+
+impl<E1: Debug, E2: Debug> AppError<E1, E2> {
+    /// Converts the error into a boxed one carrying the `Display` message,
+    /// whatever the init errors are, so that window and data init failures
+    /// of different types can be reported the same way, e.g. with `?` from a
+    /// `main` returning `Result<(), Box<dyn Error>>`. Init closures can
+    /// themselves return `Result<_, Box<dyn Error>>`.
+    pub fn into_boxed(self) -> Box<dyn Error> {
+        self.to_string().into()
+    }
+}
+
+// Init errors are only required to be `Debug`, e.g. the `()` from an `Option`,
+// so that is what is shown for them.
+impl<E1: Debug, E2: Debug> Display for AppError<E1, E2> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            AppError::EventLoopError(message) => {
+                write!(f, "failed to create the event loop: {}", message)
+            }
+            AppError::InvalidTickRate => write!(f, "the tick rate must be non-zero"),
+            AppError::UnsupportedPlatform => write!(
+                f,
+                "the event loop can't run off the main thread on this platform"
+            ),
+            AppError::WindowError(error) => write!(f, "window init failed: {:?}", error),
+            AppError::DataError(error) => write!(f, "data init failed: {:?}", error),
+        }
+    }
+}
+
+impl<E1, E2> Error for AppError<E1, E2>
+where
+    E1: Error + 'static,
+    E2: Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AppError::WindowError(error) => Some(error),
+            AppError::DataError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Returned when converting an integer into a `states!` enum fails, either
+/// because no variant has that discriminant or because the variant carries
+/// arguments and so can't be built from the integer alone.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InvalidDiscriminant(pub u32);
@@ -0,0 +1,183 @@
+//////////////////////////////////////////////////////////////////////////////
+//  File: stateloop/harness.rs
+//////////////////////////////////////////////////////////////////////////////
+//  Copyright 2017 Samuel Sleight
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+//////////////////////////////////////////////////////////////////////////////
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::config::{Phase, PhaseEdge, RunConfig};
+use crate::headless::HeadlessApp;
+use crate::state::State;
+
+/// Something the loop did during a frame run by `LoopTestHarness`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoopEvent {
+    Tick,
+    Render,
+
+    /// The frame started its ticks this far behind, as reported to
+    /// `RunConfig::on_falling_behind`.
+    FellBehind(Duration),
+}
+
+/// Runs a `HeadlessApp` on a fake clock to test how the loop keeps time under
+/// load. Ticks and renders are given a fixed cost instead of taking real
+/// time, and each frame starts `frame_time` plus the cost of the previous
+/// frame's work after the one before it. What every frame did is recorded in
+/// order, see `frames`.
+pub struct LoopTestHarness<D, S> {
+    app: HeadlessApp<D, S>,
+    events: Rc<RefCell<Vec<LoopEvent>>>,
+    frames: Vec<Vec<LoopEvent>>,
+    frame_time: Duration,
+    tick_cost: Duration,
+    render_cost: Duration,
+}
+
+impl<D, S> LoopTestHarness<D, S> {
+    /// Enters `state`, as `HeadlessApp::with_config` does. Frames start one
+    /// timestep of the configured tick rate apart, and ticks and renders cost
+    /// nothing, until set otherwise. Any profiler or `on_falling_behind`
+    /// callback already in `config` is still called.
+    pub fn new<T>(data: D, mut config: RunConfig<D, ()>, state: S) -> Self
+    where
+        S: State<D, (), T>,
+    {
+        let events = Rc::new(RefCell::new(Vec::new()));
+
+        let mut profiler = config.profiler.take();
+        let log = events.clone();
+        config.profiler = Some(Box::new(move |phase, edge, frame| {
+            match (phase, edge) {
+                (Phase::Tick, PhaseEdge::End) => log.borrow_mut().push(LoopEvent::Tick),
+                (Phase::Render, PhaseEdge::End) => log.borrow_mut().push(LoopEvent::Render),
+                _ => (),
+            }
+
+            if let Some(profiler) = profiler.as_mut() {
+                profiler(phase, edge, frame);
+            }
+        }));
+
+        let mut falling_behind = config.falling_behind.take();
+        let log = events.clone();
+        config.falling_behind = Some(Box::new(move |backlog| {
+            log.borrow_mut().push(LoopEvent::FellBehind(backlog));
+
+            if let Some(falling_behind) = falling_behind.as_mut() {
+                falling_behind(backlog);
+            }
+        }));
+
+        let app = HeadlessApp::with_config(data, config, state);
+        let frame_time = app.data().tick_interval();
+
+        // Anything the states did on entry isn't part of a frame.
+        events.borrow_mut().clear();
+
+        LoopTestHarness {
+            app,
+            events,
+            frames: Vec::new(),
+            frame_time,
+            tick_cost: Duration::ZERO,
+            render_cost: Duration::ZERO,
+        }
+    }
+
+    /// The time between frames when the previous one did no work.
+    pub fn frame_time(mut self, time: Duration) -> Self {
+        self.frame_time = time;
+        self
+    }
+
+    /// How long each tick takes on the fake clock.
+    pub fn tick_cost(mut self, cost: Duration) -> Self {
+        self.tick_cost = cost;
+        self
+    }
+
+    /// How long each render takes on the fake clock.
+    pub fn render_cost(mut self, cost: Duration) -> Self {
+        self.render_cost = cost;
+        self
+    }
+
+    /// Runs one frame, recording what it did. Returns `false` once the state
+    /// machine has quit.
+    pub fn run_frame<T>(&mut self) -> bool
+    where
+        S: State<D, (), T>,
+    {
+        let elapsed = self.frames.last().map_or(self.frame_time, |events| {
+            events
+                .iter()
+                .fold(self.frame_time, |elapsed, event| match event {
+                    LoopEvent::Tick => elapsed + self.tick_cost,
+                    LoopEvent::Render => elapsed + self.render_cost,
+                    LoopEvent::FellBehind(_) => elapsed,
+                })
+        });
+
+        let running = self.app.step_frame(elapsed);
+        self.frames
+            .push(self.events.borrow_mut().drain(..).collect());
+
+        running
+    }
+
+    /// Runs up to `count` frames, stopping early if the state machine quits.
+    /// Returns `false` once it has.
+    pub fn run_frames<T>(&mut self, count: usize) -> bool
+    where
+        S: State<D, (), T>,
+    {
+        (0..count).all(|_| self.run_frame())
+    }
+
+    /// What each frame run so far did, in order.
+    pub fn frames(&self) -> &[Vec<LoopEvent>] {
+        &self.frames
+    }
+
+    /// The number of ticks run so far.
+    pub fn tick_count(&self) -> usize {
+        self.frames
+            .iter()
+            .flatten()
+            .filter(|&&event| event == LoopEvent::Tick)
+            .count()
+    }
+
+    /// The number of frames run so far that didn't render.
+    pub fn dropped_frames(&self) -> usize {
+        self.frames
+            .iter()
+            .filter(|events| !events.contains(&LoopEvent::Render))
+            .count()
+    }
+
+    /// The app being run, e.g. to inspect the state or send events.
+    pub fn app(&self) -> &HeadlessApp<D, S> {
+        &self.app
+    }
+
+    pub fn app_mut(&mut self) -> &mut HeadlessApp<D, S> {
+        &mut self.app
+    }
+}
@@ -0,0 +1,232 @@
+//////////////////////////////////////////////////////////////////////////////
+//  File: stateloop/headless.rs
+//////////////////////////////////////////////////////////////////////////////
+//  Copyright 2017 Samuel Sleight
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+//////////////////////////////////////////////////////////////////////////////
+
+use std::time::{Duration, Instant};
+
+use crate::app::{Data, Event, LoopSnapshot, Stepper, WindowId, DEFAULT_TICK_RATE};
+use crate::config::RunConfig;
+use crate::state::{Action, State};
+
+/// Drives states without a window or event loop, e.g. to test transition
+/// logic on a machine without a display. Events, ticks and frames are fed in
+/// by hand and run through the same code as `App`, with `()` as the window.
+pub struct HeadlessApp<D, S> {
+    data: Data<D, ()>,
+    stepper: Stepper<D, (), S>,
+}
+
+impl<D, S> HeadlessApp<D, S> {
+    /// Enters `state`, as `App::run` would at the default tick rate.
+    pub fn new<T>(data: D, state: S) -> Self
+    where
+        S: State<D, (), T>,
+    {
+        Self::with_config(data, RunConfig::new(DEFAULT_TICK_RATE), state)
+    }
+
+    /// Enters `state`, as `App::run_with` would.
+    pub fn with_config<T>(data: D, config: RunConfig<D, ()>, state: S) -> Self
+    where
+        S: State<D, (), T>,
+    {
+        Self::with_stack(data, config, vec![state])
+    }
+
+    /// Enters the top of `states`, as `App::run_stack_with` would.
+    ///
+    /// # Panics
+    ///
+    /// If `states` is empty.
+    pub fn with_stack<T>(data: D, config: RunConfig<D, ()>, states: Vec<S>) -> Self
+    where
+        S: State<D, (), T>,
+    {
+        let mut data = Data::new((), data);
+        let stepper = data.start(config, states);
+
+        HeadlessApp { data, stepper }
+    }
+
+    /// Dispatches a window event to the active state, applying the action it
+    /// returns. Returns `false` once the state machine has quit, after which
+    /// further steps are ignored.
+    pub fn step_event<T>(&mut self, event: Event) -> bool
+    where
+        S: State<D, (), T>,
+    {
+        // There is no real window, and the id is only ever compared.
+        self.step_window_event(unsafe { WindowId::dummy() }, event)
+    }
+
+    /// Like `step_event`, for an event from the given window, e.g. to test a
+    /// state that handles several windows. There is no main window, so every
+    /// window's events feed the size and focus tracking.
+    pub fn step_window_event<T>(&mut self, window: WindowId, event: Event) -> bool
+    where
+        S: State<D, (), T>,
+    {
+        if !self.stepper.finished {
+            let Stepper { state, stack, .. } = &mut self.stepper;
+            let (name, handled) = (state.name(), Instant::now());
+            self.data.dispatch_event(state, stack, window, event);
+            self.data.record_state_time(name, handled.elapsed());
+            self.stepper.finished = self.data.take_quit();
+        }
+
+        !self.stepper.finished
+    }
+
+    /// Dispatches a user event to the active state's `handle_user_event`, as
+    /// if sent through a proxy from `App::create_proxy`, applying the action
+    /// it returns. Returns `false` once the state machine has quit.
+    pub fn step_user_event<T>(&mut self, event: T) -> bool
+    where
+        S: State<D, (), T>,
+    {
+        if !self.stepper.finished {
+            let Stepper { state, stack, .. } = &mut self.stepper;
+            let (name, handled) = (state.name(), Instant::now());
+            self.data.dispatch_user_event(state, stack, event);
+            self.data.record_state_time(name, handled.elapsed());
+            self.stepper.finished = self.data.take_quit();
+        }
+
+        !self.stepper.finished
+    }
+
+    /// The action the active state would return for `event`, through
+    /// `State::peek_event`, without dispatching it.
+    pub fn peek_event<T>(&self, event: &Event) -> Action<S>
+    where
+        S: State<D, (), T>,
+    {
+        let window = unsafe { WindowId::dummy() };
+        self.stepper.state.peek_event(&self.data, window, event)
+    }
+
+    /// Runs one tick of the active state's timestep, outside of any frame.
+    pub fn step_tick<T>(&mut self)
+    where
+        S: State<D, (), T>,
+    {
+        if !self.stepper.finished {
+            let Stepper {
+                config,
+                state,
+                rate,
+                ..
+            } = &mut self.stepper;
+
+            let next_rate = self.data.tick_rate_of(state);
+
+            if next_rate != *rate {
+                self.data.clear_accumulator();
+                *rate = next_rate;
+            }
+
+            let spf = self.data.retime(*rate);
+            self.data.tick(config, state, spf);
+        }
+    }
+
+    /// Runs the rest of a frame, i.e. everything `App` does after handling
+    /// events, as if `elapsed` had passed since the previous one: the ticks
+    /// that time covers, rendering and the end-of-frame checks. Returns
+    /// `false` once the state machine has quit.
+    pub fn step_frame<T>(&mut self, elapsed: Duration) -> bool
+    where
+        S: State<D, (), T>,
+    {
+        if !self.stepper.finished {
+            self.data.frame_start = Instant::now();
+
+            let now = self.data.prev_frame + elapsed;
+            self.stepper.finished = !self.data.finish_frame(&mut self.stepper, now);
+        }
+
+        !self.stepper.finished
+    }
+
+    /// Runs frames covering `duration`, each a timestep of the current tick
+    /// rate apart, so the ticks and renders that much time would have run
+    /// happen without waiting for it. Returns `false` once the state machine
+    /// has quit, stopping early.
+    pub fn advance<T>(&mut self, duration: Duration) -> bool
+    where
+        S: State<D, (), T>,
+    {
+        let mut remaining = duration;
+
+        while !remaining.is_zero() && !self.stepper.finished {
+            let elapsed = remaining.min(self.data.tick_interval());
+            remaining -= elapsed;
+
+            self.step_frame(elapsed);
+        }
+
+        !self.stepper.finished
+    }
+
+    /// How long `App` would wait after the last frame to pace the next one,
+    /// had the last taken `busy` to run. Nothing here waits for it.
+    pub fn frame_pacing(&self, busy: Duration) -> Duration {
+        self.data.frame_pacing(self.stepper.config.render_cap, busy)
+    }
+
+    /// Takes a snapshot of the run, as `App::snapshot` does.
+    pub fn snapshot(&self) -> LoopSnapshot<S>
+    where
+        S: Clone,
+    {
+        self.data.snapshot(&self.stepper)
+    }
+
+    /// Puts back a snapshot of the run, as `App::restore` does.
+    pub fn restore<T>(&mut self, snapshot: LoopSnapshot<S>)
+    where
+        S: State<D, (), T>,
+    {
+        self.data.restore(&mut self.stepper, snapshot)
+    }
+
+    /// Calls `on_shutdown` on the active state, as `App::run` does once the
+    /// loop has quit, and returns it.
+    pub fn finish<T>(mut self) -> S
+    where
+        S: State<D, (), T>,
+    {
+        self.data.shut_down(self.stepper)
+    }
+
+    /// The active state.
+    pub fn current_state(&self) -> &S {
+        &self.stepper.state
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.stepper.finished
+    }
+
+    pub fn data(&self) -> &Data<D, ()> {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut Data<D, ()> {
+        &mut self.data
+    }
+}
@@ -0,0 +1,130 @@
+//////////////////////////////////////////////////////////////////////////////
+//  File: stateloop/input.rs
+//////////////////////////////////////////////////////////////////////////////
+//  Copyright 2017 Samuel Sleight
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+//////////////////////////////////////////////////////////////////////////////
+
+use std::collections::{HashMap, HashSet};
+
+use winit::event::{ElementState, KeyboardInput};
+
+pub use winit::event::{MouseButton, ScanCode, VirtualKeyCode as Key};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+
+    /// The physical key, independent of the keyboard layout, for bindings by
+    /// position such as WASD.
+    pub scancode: ScanCode,
+
+    pub pressed: bool,
+
+    /// Whether this is an auto-repeated press of a key that is already held.
+    /// winit doesn't report this on every platform, so it is worked out from
+    /// the keys the loop has seen pressed and not yet released.
+    pub repeat: bool,
+}
+
+/// A logical action bound in an `InputMap`, passed to `handle_action`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ActionEvent {
+    pub action: &'static str,
+    pub pressed: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Input {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+impl From<Key> for Input {
+    fn from(key: Key) -> Self {
+        Input::Key(key)
+    }
+}
+
+impl From<MouseButton> for Input {
+    fn from(button: MouseButton) -> Self {
+        Input::Mouse(button)
+    }
+}
+
+/// Translates keys and mouse buttons into named actions, so states can be
+/// written in terms of actions and controls can be rebound. Key repeats
+/// aren't reported as actions.
+#[derive(Clone, Debug, Default)]
+pub struct InputMap {
+    bindings: HashMap<Input, &'static str>,
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds an input to an action, replacing any previous binding of that
+    /// input. Several inputs can be bound to the same action.
+    pub fn bind<I: Into<Input>>(&mut self, input: I, action: &'static str) -> &mut Self {
+        self.bindings.insert(input.into(), action);
+        self
+    }
+
+    pub fn unbind<I: Into<Input>>(&mut self, input: I) -> &mut Self {
+        self.bindings.remove(&input.into());
+        self
+    }
+
+    pub fn action<I: Into<Input>>(&self, input: I) -> Option<&'static str> {
+        self.bindings.get(&input.into()).copied()
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct KeyTracker {
+    held: HashSet<ScanCode>,
+    labels: HashMap<ScanCode, Key>,
+}
+
+impl KeyTracker {
+    pub(crate) fn process(&mut self, input: &KeyboardInput) -> Option<KeyEvent> {
+        let (pressed, repeat) = match input.state {
+            ElementState::Pressed => (true, !self.held.insert(input.scancode)),
+            ElementState::Released => {
+                self.held.remove(&input.scancode);
+                (false, false)
+            }
+        };
+
+        let key = input.virtual_keycode?;
+        self.labels.insert(input.scancode, key);
+
+        Some(KeyEvent {
+            key,
+            scancode: input.scancode,
+            pressed,
+            repeat,
+        })
+    }
+
+    pub(crate) fn label(&self, scancode: ScanCode) -> Option<Key> {
+        self.labels.get(&scancode).copied()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.held.clear();
+    }
+}
@@ -18,6 +18,16 @@
 
 pub extern crate winit;
 
+#[doc(hidden)]
+pub use paste;
+
 pub mod app;
+pub mod commands;
+pub mod config;
 pub mod error;
+pub mod harness;
+pub mod headless;
+pub mod input;
+pub mod render;
 pub mod state;
+pub mod window;
@@ -0,0 +1,92 @@
+//////////////////////////////////////////////////////////////////////////////
+//  File: stateloop/render.rs
+//////////////////////////////////////////////////////////////////////////////
+//  Copyright 2017 Samuel Sleight
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+//////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub enum FrameError<E> {
+    /// The surface no longer matches the window and must be recreated before
+    /// the frame can be retried.
+    OutOfDate,
+    Failed(E),
+}
+
+#[derive(Debug)]
+pub enum RenderError<E> {
+    /// The surface was still out of date after every attempt.
+    RetriesExhausted,
+    Failed(E),
+}
+
+/// The "acquire, recreate when out of date, retry" control flow of a
+/// swapchain-based renderer, giving up after a bounded number of attempts
+/// rather than spinning while e.g. the window is minimised.
+#[derive(Copy, Clone, Debug)]
+pub struct RenderLoopState {
+    max_attempts: u32,
+    recreate: bool,
+}
+
+impl RenderLoopState {
+    pub fn new(max_attempts: u32) -> RenderLoopState {
+        RenderLoopState {
+            max_attempts,
+            recreate: false,
+        }
+    }
+
+    /// Schedules a recreation before the next acquire, e.g. after a resize or
+    /// a suboptimal present.
+    pub fn request_recreate(&mut self) {
+        self.recreate = true;
+    }
+
+    pub fn needs_recreate(&self) -> bool {
+        self.recreate
+    }
+
+    /// Runs `acquire`, calling `recreate` first whenever a recreation is
+    /// pending. An `OutOfDate` error from either closure schedules a
+    /// recreation and retries, up to the configured number of attempts.
+    pub fn run<C, T, E, R, A>(
+        &mut self,
+        context: &mut C,
+        mut recreate: R,
+        mut acquire: A,
+    ) -> Result<T, RenderError<E>>
+    where
+        R: FnMut(&mut C) -> Result<(), FrameError<E>>,
+        A: FnMut(&mut C, &mut RenderLoopState) -> Result<T, FrameError<E>>,
+    {
+        for _ in 0..self.max_attempts {
+            if self.recreate {
+                match recreate(context) {
+                    Ok(()) => self.recreate = false,
+                    Err(FrameError::OutOfDate) => continue,
+                    Err(FrameError::Failed(err)) => return Err(RenderError::Failed(err)),
+                }
+            }
+
+            match acquire(context, self) {
+                Ok(value) => return Ok(value),
+                Err(FrameError::OutOfDate) => self.recreate = true,
+                Err(FrameError::Failed(err)) => return Err(RenderError::Failed(err)),
+            }
+        }
+
+        Err(RenderError::RetriesExhausted)
+    }
+}
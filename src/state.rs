@@ -16,60 +16,411 @@
 //  limitations under the License.
 //////////////////////////////////////////////////////////////////////////////
 
-use crate::app::{Data, Event};
+use std::time::Duration;
 
-#[derive(Copy, Clone)]
+use crate::app::{Data, DeviceEvent, DeviceId, Event, WindowId};
+use crate::commands::Commands;
+use crate::input::{ActionEvent, KeyEvent};
+use crate::window::ResizeSource;
+
+use winit::dpi::PhysicalSize;
+
+#[derive(Copy, Clone, Default)]
 pub enum Action<S> {
+    #[default]
     Continue,
     Done(S),
+
+    /// Switches to a new state, keeping the current one underneath it to
+    /// return to with `Pop`. Only the active state receives events, ticks
+    /// and renders.
+    Push(S),
+
+    /// Returns to the state underneath the current one, or quits if there
+    /// isn't one.
+    Pop,
+
     Quit,
+
+    /// Quits, recording a reason retrievable through `Data::quit_reason` and
+    /// printed to stderr once the run ends.
+    QuitBecause(&'static str),
 }
 
-pub trait State<D, W>: Copy {
-    fn handle_event(self, app: &mut Data<D, W>, event: Event) -> Action<Self>;
-    fn handle_tick(self, app: &mut Data<D, W>);
-    fn handle_render(self, app: &Data<D, W>);
+impl<S> Action<S> {
+    /// Runs `f` and returns this action unchanged, so a side effect can be
+    /// attached to the transition it precedes.
+    pub fn then<F: FnOnce()>(self, f: F) -> Self {
+        f();
+        self
+    }
+}
+
+pub trait State<D, W, T = ()>: Clone {
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// The tick rate to simulate at while in this state, or `None` for the
+    /// loop's rate. The accumulated backlog is dropped whenever it changes.
+    ///
+    /// # Panics
+    ///
+    /// The loop panics if this returns `Some(0)`.
+    fn tick_rate(&self, _app: &Data<D, W>) -> Option<u32> {
+        None
+    }
+
+    /// Called when this state becomes active, including for the state the
+    /// loop starts in, whenever an action switches to a different variant,
+    /// and on `Data::request_restart`. `on_exit` of the outgoing state is
+    /// called first. A `State` that isn't an enum has a single variant, so
+    /// for it these are only called at startup and on restart.
+    fn on_enter(&self, _app: &mut Data<D, W>) {}
+    fn on_exit(&self, _app: &mut Data<D, W>) {}
+
+    /// Called once on the state that was active when the loop quit, before
+    /// `App::run` returns, e.g. to wait for the GPU to idle or save files.
+    fn on_shutdown(&self, _app: &mut Data<D, W>) {}
+
+    /// `window` is the window the event is for: the main window, or one
+    /// opened with `Data::open_window`.
+    fn handle_event(&self, app: &mut Data<D, W>, window: WindowId, event: Event) -> Action<Self>;
+
+    /// The action `handle_event` would return for `event`, without applying
+    /// it, e.g. for a debugger predicting transitions. Only shared access to
+    /// the data is given, so nothing can change. Returns `Action::Continue`
+    /// unless implemented.
+    fn peek_event(&self, _app: &Data<D, W>, _window: WindowId, _event: &Event) -> Action<Self> {
+        Action::Continue
+    }
+    /// `dt` is the simulated time this tick covers: the tick interval, or a
+    /// multiple of it under `CatchupStrategy::OneBig`.
+    fn handle_tick(&self, app: &mut Data<D, W>, dt: Duration);
+    fn handle_tick_channel(&self, _app: &mut Data<D, W>, _channel: &'static str) {}
+
+    /// Called after every `handle_tick` with a queue of deferred mutations,
+    /// which the loop applies once this returns.
+    fn handle_tick_commands(&self, _app: &mut Data<D, W>, _commands: &mut Commands<Data<D, W>>) {}
+
+    /// `alpha` is how far between the last tick and the next this frame
+    /// falls, in `[0, 1]`, for interpolating what's drawn.
+    fn handle_render(&self, app: &Data<D, W>, alpha: f64);
+
+    /// Called after `handle_render` for each window opened with
+    /// `Data::open_window` that needs redrawing, see `RenderMode`. All of
+    /// them render in the same frame, so every window shows the same tick.
+    /// `Data::windows_to_redraw` lists them, e.g. to present them together.
+    fn handle_window_render(&self, _app: &Data<D, W>, _window: WindowId, _alpha: f64) {}
+
+    /// Called instead of `handle_event` when a window is asked to close.
+    /// Returning `Action::Continue` keeps the window open, e.g. to confirm
+    /// discarding unsaved changes first. By default the `CloseRequested`
+    /// event is passed on to `handle_event`.
+    fn handle_close_requested(&self, app: &mut Data<D, W>, window: WindowId) -> Action<Self> {
+        self.handle_event(app, window, Event::CloseRequested)
+    }
+
+    /// Called with the normalised key event for keyboard input, after the raw
+    /// event has been passed to `handle_event` and only if that returned
+    /// `Action::Continue`.
+    fn handle_key(&self, _app: &mut Data<D, W>, _key: KeyEvent) -> Action<Self> {
+        Action::Continue
+    }
+
+    /// Called for input bound to an action in the installed `InputMap`,
+    /// after `handle_event` and `handle_key`, and only if they returned
+    /// `Action::Continue`.
+    fn handle_action(&self, _app: &mut Data<D, W>, _action: ActionEvent) -> Action<Self> {
+        Action::Continue
+    }
+
+    fn handle_resize(
+        &self,
+        _app: &mut Data<D, W>,
+        _size: PhysicalSize<u32>,
+        _source: ResizeSource,
+    ) {
+    }
+    fn handle_focus_changed(&self, _app: &mut Data<D, W>, _focused: bool) {}
+
+    /// Called at the end of the frame in which `Data::close_window` closed
+    /// a window, to clean up anything kept for it.
+    fn handle_window_closed(&self, _app: &mut Data<D, W>, _window: WindowId) {}
+
+    /// Called with raw device input, such as unaccelerated mouse motion for
+    /// camera control. These aren't tied to the window and may arrive while
+    /// it is unfocused.
+    fn handle_device_event(
+        &self,
+        _app: &mut Data<D, W>,
+        _device: DeviceId,
+        _event: DeviceEvent,
+    ) -> Action<Self> {
+        Action::Continue
+    }
+
+    /// Called with user events sent through a proxy from
+    /// `App::create_proxy`. These are handled as they arrive, even while
+    /// window events are being deferred by `RunConfig::max_event_time`.
+    fn handle_user_event(&self, _app: &mut Data<D, W>, _event: T) -> Action<Self> {
+        Action::Continue
+    }
+
+    /// Called after the user data was rebuilt following device loss.
+    fn handle_device_lost(&self, _app: &mut Data<D, W>) {}
+
+    fn handle_content_scale_changed(&self, _app: &mut Data<D, W>, _scale: f64) {}
 }
 
 #[macro_export]
 macro_rules! states {
-    ($enum:ident { $($trait:ident $name:ident($($arg:ident: $t:ty),*)),+ }) => {
+    ($enum:ident { $($body:tt)* }) => {
+        states! { $enum<()> { $($body)* } }
+    };
+
+    ($enum:ident<$user:ty> { $($trait:ident $name:ident($($arg:ident: $t:ty),*)),+ }) => {
         states! { as_item
-            #[derive(Copy, Clone)]
+            #[derive(Clone)]
             pub enum $enum {
                 $($name($($t,)*),)+
             }
         }
 
-        $(pub trait $trait {
-            fn handle_event(&mut self, event: Event $(, $arg: $t)*) -> $crate::state::Action<$enum>;
-            fn handle_tick(&mut self $(, $arg: $t)*);
-            fn handle_render(&self $(, $arg: $t)*);
+        $crate::paste::paste! {
+            #[doc(hidden)]
+            #[repr(u32)]
+            enum [<$enum Discriminant>] {
+                $($name,)+
+            }
+
+            impl $enum {
+                pub fn name(&self) -> &'static str {
+                    match self {
+                        $($enum::$name(..) => stringify!($name),)+
+                    }
+                }
+
+                /// The index of the variant in declaration order, which is
+                /// what `TryFrom<u32>` accepts.
+                pub fn discriminant(&self) -> u32 {
+                    match self {
+                        $($enum::$name(..) => [<$enum Discriminant>]::$name as u32,)+
+                    }
+                }
+
+                $(pub fn [<is_ $name:snake>](&self) -> bool {
+                    matches!(self, $enum::$name(..))
+                })+
+            }
+
+            /// Builds a variant from its `discriminant()`. Only variants
+            /// without arguments can be built this way; the others are
+            /// rejected like unknown discriminants.
+            impl ::std::convert::TryFrom<u32> for $enum {
+                type Error = $crate::error::InvalidDiscriminant;
+
+                fn try_from(value: u32) -> Result<Self, Self::Error> {
+                    $(if value == [<$enum Discriminant>]::$name as u32 {
+                        if let Some(state) = states!(try_new $enum $name($($t),*)) {
+                            return Ok(state);
+                        }
+                    })+
+
+                    Err($crate::error::InvalidDiscriminant(value))
+                }
+            }
+        }
+
+        // Arguments are passed by reference whatever their type, so e.g. a
+        // `String` argument is taken as `&String` rather than `&str`.
+        $(#[allow(clippy::ptr_arg)]
+        pub trait $trait {
+            fn tick_rate(&self $(, _: &$t)*) -> Option<u32> {
+                None
+            }
+
+            fn on_enter(&mut self $(, _: &$t)*) {}
+            fn on_exit(&mut self $(, _: &$t)*) {}
+            fn on_shutdown(&mut self $(, _: &$t)*) {}
+
+            fn handle_event(&mut self, window: $crate::app::WindowId, event: Event $(, $arg: &$t)*) -> $crate::state::Action<$enum>;
+
+            fn peek_event(&self, _: $crate::app::WindowId, _: &$crate::app::Event $(, _: &$t)*) -> $crate::state::Action<$enum> {
+                $crate::state::Action::Continue
+            }
+
+            fn handle_tick(&mut self, dt: std::time::Duration $(, $arg: &$t)*);
+            fn handle_tick_channel(&mut self, _: &'static str $(, _: &$t)*) {}
+            fn handle_tick_commands(&mut self, _: &mut $crate::commands::Commands<Self> $(, _: &$t)*) {}
+            fn handle_render(&self, alpha: f64 $(, $arg: &$t)*);
+            fn handle_window_render(&self, _: $crate::app::WindowId, _: f64 $(, _: &$t)*) {}
+
+            fn handle_close_requested(&mut self, window: $crate::app::WindowId $(, $arg: &$t)*) -> $crate::state::Action<$enum> {
+                $trait::handle_event(self, window, Event::CloseRequested $(, $arg)*)
+            }
+
+            fn handle_key(&mut self, _: $crate::input::KeyEvent $(, _: &$t)*) -> $crate::state::Action<$enum> {
+                $crate::state::Action::Continue
+            }
+
+            fn handle_action(&mut self, _: $crate::input::ActionEvent $(, _: &$t)*) -> $crate::state::Action<$enum> {
+                $crate::state::Action::Continue
+            }
+
+            fn handle_resize(&mut self, _: $crate::winit::dpi::PhysicalSize<u32>, _: $crate::window::ResizeSource $(, _: &$t)*) {}
+            fn handle_focus_changed(&mut self, _: bool $(, _: &$t)*) {}
+            fn handle_window_closed(&mut self, _: $crate::app::WindowId $(, _: &$t)*) {}
+
+            fn handle_device_event(&mut self, _: $crate::app::DeviceId, _: $crate::app::DeviceEvent $(, _: &$t)*) -> $crate::state::Action<$enum> {
+                $crate::state::Action::Continue
+            }
+
+            fn handle_user_event(&mut self, _: $user $(, _: &$t)*) -> $crate::state::Action<$enum> {
+                $crate::state::Action::Continue
+            }
+            fn handle_device_lost(&mut self $(, _: &$t)*) {}
+            fn handle_content_scale_changed(&mut self, _: f64 $(, _: &$t)*) {}
         })+
 
         states! { as_item
-            impl<D, W> $crate::state::State<D, W> for $enum where $crate::app::Data<D, W>: $($trait +)+ Sized {
-                fn handle_event(self, app: &mut $crate::app::Data<D, W>, event: Event) -> $crate::state::Action<$enum> {
+            impl<D, W> $crate::state::State<D, W, $user> for $enum where $crate::app::Data<D, W>: $($trait +)+ Sized {
+                fn name(&self) -> &'static str {
+                    $enum::name(self)
+                }
+
+                fn tick_rate(&self, app: &$crate::app::Data<D, W>) -> Option<u32> {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::tick_rate(app $(, $arg)*),)+
+                    }
+                }
+
+                fn on_enter(&self, app: &mut $crate::app::Data<D, W>) {
                     match self {
-                        $($enum::$name($($arg),*) => $trait::handle_event(app, event $(, $arg)*),)+
+                        $($enum::$name($($arg),*) => $trait::on_enter(app $(, $arg)*),)+
                     }
                 }
 
-                fn handle_tick(self, app: &mut $crate::app::Data<D, W>) {
+                fn on_exit(&self, app: &mut $crate::app::Data<D, W>) {
                     match self {
-                        $($enum::$name($($arg),*) => $trait::handle_tick(app $(, $arg)*),)+
+                        $($enum::$name($($arg),*) => $trait::on_exit(app $(, $arg)*),)+
                     }
                 }
 
-                fn handle_render(self, app: &$crate::app::Data<D, W>) {
+                fn on_shutdown(&self, app: &mut $crate::app::Data<D, W>) {
                     match self {
-                        $($enum::$name($($arg),*) => $trait::handle_render(app $(, $arg)*),)+
+                        $($enum::$name($($arg),*) => $trait::on_shutdown(app $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_event(&self, app: &mut $crate::app::Data<D, W>, window: $crate::app::WindowId, event: Event) -> $crate::state::Action<$enum> {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_event(app, window, event $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_tick(&self, app: &mut $crate::app::Data<D, W>, dt: std::time::Duration) {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_tick(app, dt $(, $arg)*),)+
+                    }
+                }
+
+                fn peek_event(&self, app: &$crate::app::Data<D, W>, window: $crate::app::WindowId, event: &$crate::app::Event) -> $crate::state::Action<$enum> {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::peek_event(app, window, event $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_tick_channel(&self, app: &mut $crate::app::Data<D, W>, channel: &'static str) {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_tick_channel(app, channel $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_tick_commands(&self, app: &mut $crate::app::Data<D, W>, commands: &mut $crate::commands::Commands<$crate::app::Data<D, W>>) {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_tick_commands(app, commands $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_render(&self, app: &$crate::app::Data<D, W>, alpha: f64) {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_render(app, alpha $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_window_render(&self, app: &$crate::app::Data<D, W>, window: $crate::app::WindowId, alpha: f64) {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_window_render(app, window, alpha $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_close_requested(&self, app: &mut $crate::app::Data<D, W>, window: $crate::app::WindowId) -> $crate::state::Action<$enum> {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_close_requested(app, window $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_key(&self, app: &mut $crate::app::Data<D, W>, key: $crate::input::KeyEvent) -> $crate::state::Action<$enum> {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_key(app, key $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_resize(&self, app: &mut $crate::app::Data<D, W>, size: $crate::winit::dpi::PhysicalSize<u32>, source: $crate::window::ResizeSource) {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_resize(app, size, source $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_action(&self, app: &mut $crate::app::Data<D, W>, action: $crate::input::ActionEvent) -> $crate::state::Action<$enum> {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_action(app, action $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_focus_changed(&self, app: &mut $crate::app::Data<D, W>, focused: bool) {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_focus_changed(app, focused $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_device_event(&self, app: &mut $crate::app::Data<D, W>, device: $crate::app::DeviceId, event: $crate::app::DeviceEvent) -> $crate::state::Action<$enum> {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_device_event(app, device, event $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_user_event(&self, app: &mut $crate::app::Data<D, W>, event: $user) -> $crate::state::Action<$enum> {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_user_event(app, event $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_window_closed(&self, app: &mut $crate::app::Data<D, W>, window: $crate::app::WindowId) {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_window_closed(app, window $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_device_lost(&self, app: &mut $crate::app::Data<D, W>) {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_device_lost(app $(, $arg)*),)+
+                    }
+                }
+
+                fn handle_content_scale_changed(&self, app: &mut $crate::app::Data<D, W>, scale: f64) {
+                    match self {
+                        $($enum::$name($($arg),*) => $trait::handle_content_scale_changed(app, scale $(, $arg)*),)+
                     }
                 }
             }
         }
     };
 
+    (try_new $enum:ident $name:ident()) => { Some($enum::$name()) };
+    (try_new $enum:ident $name:ident($($t:ty),+)) => { None };
+
     (trait_bounds $trait:ident) => { $trait };
     (trait_bounds $trait:ident $(, $traits:ident)+) => { $trait + states!(trait_bounds $($traits),+) };
 
@@ -0,0 +1,107 @@
+//////////////////////////////////////////////////////////////////////////////
+//  File: stateloop/window.rs
+//////////////////////////////////////////////////////////////////////////////
+//  Copyright 2017 Samuel Sleight
+//
+//  Licensed under the Apache License, Version 2.0 (the "License");
+//  you may not use this file except in compliance with the License.
+//  You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+//  Unless required by applicable law or agreed to in writing, software
+//  distributed under the License is distributed on an "AS IS" BASIS,
+//  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//  See the License for the specific language governing permissions and
+//  limitations under the License.
+//////////////////////////////////////////////////////////////////////////////
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+use winit::dpi::Size;
+use winit::window::{CursorIcon, Window};
+
+#[cfg(feature = "raw-window-handle")]
+use crate::app::Data;
+
+/// Access to the underlying `winit` window of whatever the window init
+/// closure produced, so the loop can query and update it.
+pub trait AsWindow {
+    fn as_window(&self) -> &Window;
+}
+
+impl AsWindow for Window {
+    fn as_window(&self) -> &Window {
+        self
+    }
+}
+
+impl<T: AsWindow> AsWindow for Arc<T> {
+    fn as_window(&self) -> &Window {
+        (**self).as_window()
+    }
+}
+
+impl<T: AsWindow> AsWindow for Rc<T> {
+    fn as_window(&self) -> &Window {
+        (**self).as_window()
+    }
+}
+
+/// What caused a window resize reported to `handle_resize`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResizeSource {
+    /// The user or the platform, e.g. dragging the window border.
+    User,
+
+    /// A call to `Data::set_inner_size`, so handlers can ignore their own
+    /// changes.
+    Programmatic,
+
+    /// The loop reporting the window's size once before the first frame, see
+    /// `RunConfig::initial_resize`.
+    Initial,
+}
+
+/// Window changes collected by `Data::window_batch`. Setting the same
+/// property more than once keeps only the last value.
+#[derive(Default)]
+pub struct WindowOps {
+    pub(crate) title: Option<String>,
+    pub(crate) cursor_icon: Option<CursorIcon>,
+    pub(crate) inner_size: Option<Size>,
+    pub(crate) min_inner_size: Option<Option<Size>>,
+}
+
+impl WindowOps {
+    pub fn set_title(&mut self, title: &str) {
+        self.title = Some(title.to_owned());
+    }
+
+    pub fn set_cursor_icon(&mut self, icon: CursorIcon) {
+        self.cursor_icon = Some(icon);
+    }
+
+    pub fn set_inner_size<S: Into<Size>>(&mut self, size: S) {
+        self.inner_size = Some(size.into());
+    }
+
+    pub fn set_min_inner_size<S: Into<Size>>(&mut self, size: Option<S>) {
+        self.min_inner_size = Some(size.map(Into::into));
+    }
+}
+
+#[cfg(feature = "vulkano")]
+impl AsWindow for vulkano::swapchain::Surface<Window> {
+    fn as_window(&self) -> &Window {
+        self.window()
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+unsafe impl<D, W: AsWindow> raw_window_handle::HasRawWindowHandle for Data<D, W> {
+    fn raw_window_handle(&self) -> raw_window_handle::RawWindowHandle {
+        self.window().as_window().raw_window_handle()
+    }
+}
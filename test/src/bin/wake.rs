@@ -0,0 +1,62 @@
+use std::{thread, time::Duration};
+
+use stateloop::{
+    app::{App, Data, Event, Window, WindowId},
+    config::{EventMode, RunConfig},
+    state::Action,
+    states,
+    winit::dpi::LogicalSize,
+};
+
+states! {
+    State {
+        IdleHandler Idle()
+    }
+}
+
+impl IdleHandler for Data<u32, Window> {
+    fn handle_event(&mut self, _: WindowId, event: Event) -> Action<State> {
+        match event {
+            Event::CloseRequested => Action::Quit,
+            _ => Action::Continue,
+        }
+    }
+
+    fn handle_tick(&mut self, _: Duration) {}
+
+    fn handle_user_event(&mut self, _: ()) -> Action<State> {
+        self.data += 1;
+        println!("Woken {} times", self.data);
+
+        if self.data == 5 {
+            Action::Quit
+        } else {
+            Action::Continue
+        }
+    }
+
+    fn handle_render(&self, _: f64) {}
+}
+
+fn main() {
+    let mut app = App::windowed()
+        .title("Wake Test")
+        .inner_size(LogicalSize::new(300, 200))
+        .build(|builder, event_loop| builder.build(event_loop), |_| 0)
+        .unwrap();
+
+    // The loop sleeps until an event arrives, so these wake-ups from another
+    // thread are the only thing that makes it run.
+    let proxy = app.create_proxy();
+
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+
+        if proxy.send_event(()).is_err() {
+            break;
+        }
+    });
+
+    let config = RunConfig::new(60).event_mode(EventMode::Wait);
+    app.run_with(config, State::Idle());
+}
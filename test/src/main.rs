@@ -2,12 +2,15 @@ use std::{
     cell::{RefCell, UnsafeCell},
     ptr,
     sync::Arc,
+    time::Duration,
 };
 
 use stateloop::{
-    app::{App, Data, Event, Window, WindowBuilder},
+    app::{App, Data, Event, PhysicalSize, Window, WindowId},
+    render::{FrameError, RenderError, RenderLoopState},
     state::Action,
     states,
+    window::ResizeSource,
     winit::dpi::LogicalSize,
 };
 
@@ -53,6 +56,7 @@ stateloop::states! {
 
 struct Renderer {
     data: RefCell<RendererData>,
+    render_loop: RefCell<RenderLoopState>,
 }
 
 struct RendererData {
@@ -68,20 +72,24 @@ struct RendererData {
 
     viewport: Viewport,
     frame_future: UnsafeCell<Box<dyn GpuFuture>>,
-    recreate_swapchain: bool,
 }
 
 impl MainHandler for Data<Renderer, Arc<Surface<Window>>> {
-    fn handle_event(&mut self, event: Event) -> Action<State> {
+    fn handle_event(&mut self, _: WindowId, event: Event) -> Action<State> {
         match event {
             Event::CloseRequested => Action::Quit,
             _ => Action::Continue,
         }
     }
 
-    fn handle_tick(&mut self) {}
+    fn handle_tick(&mut self, _: Duration) {}
 
-    fn handle_render(&self) {
+    fn handle_resize(&mut self, size: PhysicalSize<u32>, _: ResizeSource) {
+        let mut renderer = self.data.data.borrow_mut();
+        renderer.viewport.dimensions = [size.width as f32, size.height as f32];
+    }
+
+    fn handle_render(&self, _: f64) {
         let mut renderer = self.data.data.borrow_mut();
 
         let mut frame_future = unsafe {
@@ -91,131 +99,141 @@ impl MainHandler for Data<Renderer, Arc<Surface<Window>>> {
 
         frame_future.cleanup_finished();
 
-        loop {
-            if renderer.recreate_swapchain {
-                let dimensions = self.window().window().inner_size();
+        let acquired = self.data.render_loop.borrow_mut().run(
+            &mut *renderer,
+            |renderer| {
+                let dimensions = self.inner_size();
 
-                let (new_swapchain, new_images) =
-                    match renderer.swapchain.recreate(SwapchainCreateInfo {
+                let (new_swapchain, new_images) = renderer
+                    .swapchain
+                    .recreate(SwapchainCreateInfo {
                         image_extent: dimensions.into(),
                         ..renderer.swapchain.create_info()
-                    }) {
-                        Ok(r) => r,
-                        Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => continue,
-                        Err(err) => panic!("{:?}", err),
-                    };
+                    })
+                    .map_err(|err| match err {
+                        SwapchainCreationError::ImageExtentNotSupported { .. } => {
+                            FrameError::OutOfDate
+                        }
+                        err => FrameError::Failed(format!("{:?}", err)),
+                    })?;
 
                 renderer.swapchain = new_swapchain;
                 renderer.images = new_images;
                 renderer.framebuffers = None;
-                renderer.recreate_swapchain = false;
-            }
 
-            if renderer.framebuffers.is_none() {
-                let [w, h] = renderer.images[0].dimensions().width_height();
-                renderer.viewport.dimensions = [w as f32, h as f32];
-
-                let new_framebuffers = Some(
-                    renderer
-                        .images
-                        .iter()
-                        .map(|image| {
-                            let view = ImageView::new_default(image.clone()).unwrap();
-                            Framebuffer::new(
-                                renderer.render_pass.clone(),
-                                FramebufferCreateInfo {
-                                    attachments: vec![view],
-                                    ..Default::default()
-                                },
-                            )
-                            .unwrap()
-                        })
-                        .collect::<Vec<_>>(),
-                );
+                Ok(())
+            },
+            |renderer, render_loop| {
+                if renderer.framebuffers.is_none() {
+                    let [w, h] = renderer.images[0].dimensions().width_height();
+                    renderer.viewport.dimensions = [w as f32, h as f32];
+
+                    let new_framebuffers = Some(
+                        renderer
+                            .images
+                            .iter()
+                            .map(|image| {
+                                let view = ImageView::new_default(image.clone()).unwrap();
+                                Framebuffer::new(
+                                    renderer.render_pass.clone(),
+                                    FramebufferCreateInfo {
+                                        attachments: vec![view],
+                                        ..Default::default()
+                                    },
+                                )
+                                .unwrap()
+                            })
+                            .collect::<Vec<_>>(),
+                    );
+
+                    renderer.framebuffers = new_framebuffers;
+                }
 
-                renderer.framebuffers = new_framebuffers;
-            }
+                let (image_num, suboptimal, acquire_future) =
+                    swapchain::acquire_next_image(renderer.swapchain.clone(), None).map_err(
+                        |err| match err {
+                            AcquireError::OutOfDate => FrameError::OutOfDate,
+                            err => FrameError::Failed(format!("{:?}", err)),
+                        },
+                    )?;
 
-            let (image_num, suboptimal, acquire_future) =
-                match swapchain::acquire_next_image(renderer.swapchain.clone(), None) {
-                    Ok(r) => r,
-                    Err(AcquireError::OutOfDate) => {
-                        renderer.recreate_swapchain = true;
-                        continue;
-                    }
-                    Err(err) => panic!("{:?}", err),
-                };
+                if suboptimal {
+                    render_loop.request_recreate();
+                }
 
-            if suboptimal {
-                renderer.recreate_swapchain = true;
-            }
+                Ok((image_num, acquire_future))
+            },
+        );
 
-            let mut builder = AutoCommandBufferBuilder::primary(
-                renderer.device.clone(),
-                renderer.queue.family(),
-                CommandBufferUsage::OneTimeSubmit,
-            )
-            .unwrap();
-
-            builder
-                .begin_render_pass(
-                    RenderPassBeginInfo {
-                        clear_values: vec![Some([1.0, 0.0, 1.0, 1.0].into())],
-                        ..RenderPassBeginInfo::framebuffer(
-                            renderer.framebuffers.as_ref().unwrap()[image_num].clone(),
-                        )
-                    },
-                    SubpassContents::Inline,
+        let end_future = match acquired {
+            Ok((image_num, acquire_future)) => {
+                let mut builder = AutoCommandBufferBuilder::primary(
+                    renderer.device.clone(),
+                    renderer.queue.family(),
+                    CommandBufferUsage::OneTimeSubmit,
                 )
-                .unwrap()
-                .set_viewport(0, [renderer.viewport.clone()])
-                .bind_pipeline_graphics(renderer.pipeline.clone())
-                .bind_vertex_buffers(0, renderer.vertex_buffer.clone())
-                .draw(renderer.vertex_buffer.len() as u32, 1, 0, 0)
-                .unwrap()
-                .end_render_pass()
                 .unwrap();
 
-            let command_buffer = builder.build().unwrap();
-
-            let future = frame_future
-                .join(acquire_future)
-                .then_execute(renderer.queue.clone(), command_buffer)
-                .unwrap()
-                .then_swapchain_present(
-                    renderer.queue.clone(),
-                    renderer.swapchain.clone(),
-                    image_num,
-                )
-                .then_signal_fence_and_flush();
-
-            let end_future = match future {
-                Ok(future) => Box::new(future) as Box<_>,
-                Err(FlushError::OutOfDate) => {
-                    renderer.recreate_swapchain = true;
-                    Box::new(now(renderer.device.clone())) as Box<_>
+                builder
+                    .begin_render_pass(
+                        RenderPassBeginInfo {
+                            clear_values: vec![Some([1.0, 0.0, 1.0, 1.0].into())],
+                            ..RenderPassBeginInfo::framebuffer(
+                                renderer.framebuffers.as_ref().unwrap()[image_num].clone(),
+                            )
+                        },
+                        SubpassContents::Inline,
+                    )
+                    .unwrap()
+                    .set_viewport(0, [renderer.viewport.clone()])
+                    .bind_pipeline_graphics(renderer.pipeline.clone())
+                    .bind_vertex_buffers(0, renderer.vertex_buffer.clone())
+                    .draw(renderer.vertex_buffer.len() as u32, 1, 0, 0)
+                    .unwrap()
+                    .end_render_pass()
+                    .unwrap();
+
+                let command_buffer = builder.build().unwrap();
+
+                let future = frame_future
+                    .join(acquire_future)
+                    .then_execute(renderer.queue.clone(), command_buffer)
+                    .unwrap()
+                    .then_swapchain_present(
+                        renderer.queue.clone(),
+                        renderer.swapchain.clone(),
+                        image_num,
+                    )
+                    .then_signal_fence_and_flush();
+
+                match future {
+                    Ok(future) => Box::new(future) as Box<_>,
+                    Err(FlushError::OutOfDate) => {
+                        self.data.render_loop.borrow_mut().request_recreate();
+                        Box::new(now(renderer.device.clone())) as Box<_>
+                    }
+                    Err(_) => Box::new(now(renderer.device.clone())) as Box<_>,
                 }
-                Err(_) => Box::new(now(renderer.device.clone())) as Box<_>,
-            };
-
-            unsafe {
-                let ptr = renderer.frame_future.get();
-                ptr::write(ptr, end_future);
             }
+            Err(RenderError::RetriesExhausted) => frame_future,
+            Err(RenderError::Failed(err)) => panic!("{}", err),
+        };
 
-            break;
+        unsafe {
+            let ptr = renderer.frame_future.get();
+            ptr::write(ptr, end_future);
         }
     }
 }
 
 impl TestHandler for Data<Renderer, Arc<Surface<Window>>> {
-    fn handle_event(&mut self, _: Event, _: usize) -> Action<State> {
+    fn handle_event(&mut self, _: WindowId, _: Event, _: &usize) -> Action<State> {
         Action::Done(State::Main())
     }
 
-    fn handle_tick(&mut self, _: usize) {}
+    fn handle_tick(&mut self, _: Duration, _: &usize) {}
 
-    fn handle_render(&self, _: usize) {}
+    fn handle_render(&self, _: f64, _: &usize) {}
 }
 
 fn init_vulkan(instance: Arc<Instance>, window: &Arc<Surface<Window>>) -> Renderer {
@@ -397,8 +415,8 @@ void main() {
 
             viewport,
             frame_future: UnsafeCell::new(Box::new(now(device.clone())) as Box<dyn GpuFuture>),
-            recreate_swapchain: false,
         }),
+        render_loop: RefCell::new(RenderLoopState::new(5)),
     }
 }
 
@@ -416,15 +434,13 @@ fn main() {
 
     let i = instance.clone();
 
-    App::new(
-        |event_loop| {
-            WindowBuilder::new()
-                .with_title("States Test")
-                .with_inner_size(LogicalSize::new(500, 500))
-                .build_vk_surface(event_loop, i)
-        },
-        |window| init_vulkan(instance, window),
-    )
-    .unwrap()
-    .run(60, State::Test(15))
+    App::windowed()
+        .title("States Test")
+        .inner_size(LogicalSize::new(500, 500))
+        .build(
+            |builder, event_loop| builder.build_vk_surface(event_loop, i),
+            |window| init_vulkan(instance, window),
+        )
+        .unwrap()
+        .run(60, State::Test(15));
 }
@@ -0,0 +1,70 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use stateloop::error::{AppError, MaybeResult, NoError};
+
+#[derive(Debug)]
+struct NoDevice;
+
+impl Display for NoDevice {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "no device")
+    }
+}
+
+impl Error for NoDevice {}
+
+#[test]
+fn app_error_names_the_failed_init() {
+    let error: AppError<NoDevice, NoDevice> = AppError::WindowError(NoDevice);
+    assert_eq!(error.to_string(), "window init failed: NoDevice");
+
+    let error: AppError<NoDevice, NoDevice> = AppError::DataError(NoDevice);
+    assert_eq!(error.to_string(), "data init failed: NoDevice");
+}
+
+#[test]
+fn app_error_source_is_the_init_error() {
+    let error: AppError<NoDevice, NoDevice> = AppError::DataError(NoDevice);
+    assert_eq!(error.source().unwrap().to_string(), "no device");
+
+    let error: AppError<NoDevice, NoDevice> = AppError::InvalidTickRate;
+    assert!(error.source().is_none());
+}
+
+#[test]
+fn plain_values_never_fail() {
+    let result: Result<u32, NoError> = 5.as_result();
+    assert!(matches!(result, Ok(5)));
+}
+
+#[test]
+fn options_fail_with_unit() {
+    assert_eq!(MaybeResult::<u32>::as_result(Some(5)), Ok(5));
+    assert_eq!(MaybeResult::<u32>::as_result(None::<u32>), Err(()));
+}
+
+#[test]
+fn results_keep_their_error() {
+    let result: Result<u32, String> = Err("missing".into());
+    assert_eq!(
+        MaybeResult::<u32>::as_result(result),
+        Err("missing".to_string())
+    );
+}
+
+#[test]
+fn boxed_errors_pass_through() {
+    let result: Result<u32, Box<dyn Error>> = Err(Box::new(NoDevice));
+    let error = MaybeResult::<u32>::as_result(result).unwrap_err();
+    assert_eq!(error.to_string(), "no device");
+}
+
+#[test]
+fn app_error_converts_into_a_boxed_error() {
+    let error: AppError<(), String> = AppError::DataError("missing".into());
+    assert_eq!(
+        error.into_boxed().to_string(),
+        "data init failed: \"missing\""
+    );
+}
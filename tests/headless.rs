@@ -0,0 +1,251 @@
+use std::time::Duration;
+
+use stateloop::app::{Data, Event, WindowId};
+use stateloop::config::{DuplicateStates, RunConfig};
+use stateloop::headless::HeadlessApp;
+use stateloop::state::Action;
+use stateloop::states;
+
+#[derive(Default)]
+struct Log {
+    ticks: Vec<(String, Duration)>,
+    windows: Vec<WindowId>,
+}
+
+states! {
+    State {
+        MenuHandler Menu(),
+        LevelHandler Level(name: String),
+        PausedHandler Paused()
+    }
+}
+
+type App = Data<Log, ()>;
+
+impl MenuHandler for App {
+    fn handle_event(&mut self, window: WindowId, event: Event) -> Action<State> {
+        self.split_mut().1.windows.push(window);
+
+        match event {
+            Event::ReceivedCharacter('p') => Action::Done(State::Level("one".into())),
+            Event::CloseRequested => Action::Quit,
+            _ => Action::Continue,
+        }
+    }
+
+    fn peek_event(&self, _: WindowId, event: &Event) -> Action<State> {
+        match event {
+            Event::ReceivedCharacter('p') => Action::Done(State::Level("one".into())),
+            _ => Action::Continue,
+        }
+    }
+
+    fn handle_tick(&mut self, dt: Duration) {
+        self.split_mut().1.ticks.push(("menu".into(), dt));
+    }
+
+    fn handle_render(&self, _: f64) {}
+}
+
+impl LevelHandler for App {
+    fn handle_event(&mut self, _: WindowId, event: Event, _: &String) -> Action<State> {
+        match event {
+            Event::ReceivedCharacter('p') => Action::Push(State::Paused()),
+            Event::ReceivedCharacter('q') => Action::Quit,
+            _ => Action::Continue,
+        }
+    }
+
+    // Unsaved progress: closing the window is vetoed mid-level.
+    fn handle_close_requested(&mut self, _: WindowId, _: &String) -> Action<State> {
+        Action::Continue
+    }
+
+    fn handle_tick(&mut self, dt: Duration, name: &String) {
+        self.split_mut().1.ticks.push((name.clone(), dt));
+    }
+
+    fn handle_render(&self, _: f64, _: &String) {}
+}
+
+impl PausedHandler for App {
+    fn tick_rate(&self) -> Option<u32> {
+        Some(10)
+    }
+
+    fn handle_event(&mut self, _: WindowId, event: Event) -> Action<State> {
+        match event {
+            Event::ReceivedCharacter('p') => Action::Pop,
+            Event::ReceivedCharacter('d') => Action::Push(State::Paused()),
+            _ => Action::Continue,
+        }
+    }
+
+    fn handle_tick(&mut self, dt: Duration) {
+        self.split_mut().1.ticks.push(("paused".into(), dt));
+    }
+
+    fn handle_render(&self, _: f64) {}
+}
+
+fn key(c: char) -> Event<'static> {
+    Event::ReceivedCharacter(c)
+}
+
+#[test]
+fn scripted_events_drive_transitions() {
+    let mut app = HeadlessApp::new(Log::default(), State::Menu());
+
+    assert!(app.step_event(key('x')));
+    assert!(app.current_state().is_menu());
+
+    assert!(app.step_event(key('p')));
+    assert!(matches!(app.current_state(), State::Level(name) if name == "one"));
+
+    assert!(app.step_event(key('p')));
+    assert!(app.current_state().is_paused());
+
+    assert!(app.step_event(key('p')));
+    assert!(matches!(app.current_state(), State::Level(name) if name == "one"));
+
+    assert!(!app.step_event(key('q')));
+    assert!(app.is_finished());
+
+    // Further steps are ignored once finished.
+    assert!(!app.step_event(key('p')));
+    assert!(app.current_state().is_level());
+}
+
+#[test]
+fn step_tick_uses_the_state_tick_rate() {
+    let mut app = HeadlessApp::new(Log::default(), State::Level("one".into()));
+
+    app.step_tick();
+    app.step_event(key('p'));
+    app.step_tick();
+
+    let ticks = &app.data_mut().split_mut().1.ticks;
+    assert_eq!(
+        ticks[0],
+        ("one".into(), Duration::from_secs_f64(1.0 / 60.0))
+    );
+    assert_eq!(ticks[1], ("paused".into(), Duration::from_millis(100)));
+}
+
+#[test]
+fn step_frame_runs_the_ticks_elapsed_time_covers() {
+    let mut app = HeadlessApp::new(Log::default(), State::Menu());
+
+    assert!(app.step_frame(Duration::from_millis(51)));
+    assert_eq!(app.data_mut().split_mut().1.ticks.len(), 3);
+}
+
+#[test]
+fn target_fps_reports_the_state_tick_rate() {
+    let mut app = HeadlessApp::new(Log::default(), State::Level("one".into()));
+
+    assert_eq!(app.data().target_fps(), 60);
+
+    app.step_event(key('p'));
+    app.step_frame(Duration::ZERO);
+
+    assert_eq!(app.data().target_fps(), 10);
+    assert_eq!(app.data().tick_interval(), Duration::from_millis(100));
+}
+
+#[test]
+fn close_hook_returning_continue_keeps_running() {
+    let mut app = HeadlessApp::new(Log::default(), State::Level("one".into()));
+
+    assert!(app.step_event(Event::CloseRequested));
+    assert!(app.step_frame(Duration::ZERO));
+    assert!(app.current_state().is_level());
+
+    let mut app = HeadlessApp::new(Log::default(), State::Menu());
+    assert!(!app.step_event(Event::CloseRequested));
+}
+
+#[test]
+fn push_and_pop_return_to_the_state_below() {
+    let mut app = HeadlessApp::new(Log::default(), State::Level("two".into()));
+
+    app.step_event(key('p'));
+    assert!(app.current_state().is_paused());
+
+    // Only the top of the stack ticks.
+    app.step_tick();
+    assert_eq!(app.data_mut().split_mut().1.ticks[0].0, "paused");
+
+    assert!(app.step_event(key('p')));
+    assert!(matches!(app.current_state(), State::Level(name) if name == "two"));
+}
+
+#[test]
+fn pop_with_nothing_below_quits() {
+    let mut app = HeadlessApp::new(Log::default(), State::Paused());
+
+    assert!(!app.step_event(key('p')));
+    assert!(app.is_finished());
+}
+
+#[test]
+fn events_reach_the_state_with_their_window_id() {
+    let mut app = HeadlessApp::new(Log::default(), State::Menu());
+    let window = unsafe { WindowId::dummy() };
+
+    app.step_window_event(window, key('x'));
+    app.step_event(key('x'));
+
+    assert_eq!(app.data_mut().split_mut().1.windows, [window, window]);
+}
+
+#[test]
+fn string_payload_survives_a_done_transition() {
+    let mut app = HeadlessApp::new(Log::default(), State::Menu());
+
+    app.step_event(key('p'));
+    app.step_tick();
+
+    assert!(matches!(app.current_state(), State::Level(name) if name == "one"));
+    assert_eq!(app.data_mut().split_mut().1.ticks[0].0, "one");
+}
+
+#[test]
+fn runs_can_start_from_a_stack() {
+    let states = vec![State::Level("saved".into()), State::Paused()];
+    let mut app = HeadlessApp::with_stack(Log::default(), RunConfig::new(60), states);
+
+    assert!(app.current_state().is_paused());
+
+    assert!(app.step_event(key('p')));
+    assert!(matches!(app.current_state(), State::Level(name) if name == "saved"));
+}
+
+#[test]
+#[should_panic(expected = "Paused pushed while already on the stack")]
+fn duplicate_pushes_can_be_forbidden() {
+    let config = RunConfig::new(60).forbid_duplicate_stack_states(DuplicateStates::Panic);
+    let states = vec![State::Level("one".into()), State::Paused()];
+    let mut app = HeadlessApp::with_stack(Log::default(), config, states);
+
+    app.step_event(key('d'));
+}
+
+#[test]
+fn duplicate_pushes_are_allowed_by_default() {
+    let mut app = HeadlessApp::new(Log::default(), State::Paused());
+
+    assert!(app.step_event(key('d')));
+    assert!(app.step_event(key('p')));
+    assert!(app.current_state().is_paused());
+}
+
+#[test]
+fn peeking_an_event_changes_nothing() {
+    let mut app = HeadlessApp::new(Log::default(), State::Menu());
+
+    let action = app.peek_event(&key('p'));
+    assert!(matches!(action, Action::Done(State::Level(name)) if name == "one"));
+    assert!(app.current_state().is_menu());
+    assert!(app.data_mut().split_mut().1.windows.is_empty());
+}
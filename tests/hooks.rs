@@ -0,0 +1,205 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use stateloop::app::{Data, Event, WindowId};
+use stateloop::config::RunConfig;
+use stateloop::headless::HeadlessApp;
+use stateloop::state::Action;
+use stateloop::states;
+
+// Shared, so that it can still be read once `finish` has consumed the app.
+#[derive(Clone, Default)]
+struct Log {
+    calls: Rc<RefCell<Vec<&'static str>>>,
+}
+
+states! {
+    State {
+        TitleHandler Title(),
+        GameHandler Game()
+    }
+}
+
+type App = Data<Log, ()>;
+
+impl Log {
+    fn push(&self, call: &'static str) {
+        self.calls.borrow_mut().push(call);
+    }
+
+    fn take(&self) -> Vec<&'static str> {
+        self.calls.take()
+    }
+}
+
+impl TitleHandler for App {
+    fn on_enter(&mut self) {
+        self.data.push("enter title");
+    }
+
+    fn on_exit(&mut self) {
+        self.data.push("exit title");
+    }
+
+    fn handle_event(&mut self, _: WindowId, event: Event) -> Action<State> {
+        match event {
+            Event::ReceivedCharacter('g') => Action::Done(State::Game()),
+            Event::ReceivedCharacter('t') => Action::Done(State::Title()),
+            Event::ReceivedCharacter('r') => {
+                self.request_restart();
+                Action::Continue
+            }
+            _ => Action::Continue,
+        }
+    }
+
+    fn handle_tick(&mut self, _: Duration) {
+        self.data.push("tick title");
+    }
+
+    fn handle_render(&self, _: f64) {
+        self.data.push("render title");
+    }
+}
+
+impl GameHandler for App {
+    fn on_enter(&mut self) {
+        self.data.push("enter game");
+    }
+
+    fn on_exit(&mut self) {
+        self.data.push("exit game");
+    }
+
+    fn on_shutdown(&mut self) {
+        self.data.push("shutdown game");
+    }
+
+    fn handle_event(&mut self, _: WindowId, event: Event) -> Action<State> {
+        match event {
+            Event::ReceivedCharacter('q') => Action::Quit,
+            Event::ReceivedCharacter('r') => {
+                self.request_restart();
+                Action::Continue
+            }
+            _ => Action::Continue,
+        }
+    }
+
+    fn handle_tick(&mut self, _: Duration) {
+        self.data.push("tick game");
+    }
+
+    fn handle_render(&self, _: f64) {
+        self.data.push("render game");
+    }
+}
+
+fn key(c: char) -> Event<'static> {
+    Event::ReceivedCharacter(c)
+}
+
+// The hook calls logged since the last check, without ticks and renders.
+fn transitions(app: &HeadlessApp<Log, State>) -> Vec<&'static str> {
+    let mut log = app.data().data.take();
+    log.retain(|call| call.starts_with("enter") || call.starts_with("exit"));
+    log
+}
+
+#[test]
+fn switching_variant_exits_before_entering() {
+    let mut app = HeadlessApp::new(Log::default(), State::Title());
+    app.data().data.take();
+
+    app.step_event(key('g'));
+    assert_eq!(app.data().data.take(), ["exit title", "enter game"]);
+}
+
+#[test]
+fn switching_to_the_same_variant_skips_the_hooks() {
+    let mut app = HeadlessApp::new(Log::default(), State::Title());
+    app.data().data.take();
+
+    app.step_event(key('t'));
+    assert!(app.data().data.take().is_empty());
+}
+
+#[test]
+fn restart_reenters_the_initial_state() {
+    let mut app = HeadlessApp::new(Log::default(), State::Title());
+    app.data().data.take();
+
+    app.step_event(key('r'));
+    app.step_frame(Duration::ZERO);
+
+    assert_eq!(transitions(&app), ["exit title", "enter title"]);
+}
+
+#[test]
+fn restart_from_another_state_returns_to_the_initial_one() {
+    let mut app = HeadlessApp::new(Log::default(), State::Title());
+
+    app.step_event(key('g'));
+    app.data().data.take();
+
+    app.step_event(key('r'));
+    app.step_frame(Duration::ZERO);
+
+    assert!(app.current_state().is_title());
+    assert_eq!(transitions(&app), ["exit game", "enter title"]);
+}
+
+#[test]
+fn first_on_enter_runs_once_before_any_tick_or_render() {
+    let mut app = HeadlessApp::new(Log::default(), State::Title());
+
+    app.step_frame(Duration::from_millis(51));
+    app.step_frame(Duration::from_millis(51));
+
+    let log = app.data().data.take();
+    assert_eq!(log[0], "enter title");
+    assert_eq!(log.iter().filter(|&&call| call == "enter title").count(), 1);
+    assert!(log[1..].contains(&"tick title"));
+    assert!(log[1..].contains(&"render title"));
+}
+
+#[test]
+fn finish_returns_the_state_that_quit() {
+    let mut app = HeadlessApp::new(Log::default(), State::Title());
+
+    app.step_event(key('g'));
+    assert!(!app.step_event(key('q')));
+
+    assert!(app.finish().is_game());
+}
+
+#[test]
+fn on_shutdown_runs_once_after_quitting() {
+    let log = Log::default();
+    let mut app = HeadlessApp::new(log.clone(), State::Game());
+
+    app.step_event(key('q'));
+    app.step_event(key('q'));
+    assert!(!log.take().contains(&"shutdown game"));
+
+    app.finish();
+    assert_eq!(log.take(), ["shutdown game"]);
+}
+
+#[test]
+fn restart_exits_with_the_old_data_and_enters_with_the_new() {
+    let old = Log::default();
+    let new = Log::default();
+
+    let rebuilt = new.clone();
+    let config = RunConfig::new(60).data_init(move |_| rebuilt.clone());
+    let mut app = HeadlessApp::with_config(old.clone(), config, State::Game());
+    old.take();
+
+    app.step_event(key('r'));
+    app.step_frame(Duration::ZERO);
+
+    assert_eq!(old.take(), ["render game", "exit game"]);
+    assert_eq!(new.take(), ["enter game"]);
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use stateloop::app::{Data, Event, WindowId};
+use stateloop::error::InvalidDiscriminant;
+use stateloop::state::Action;
+use stateloop::states;
+
+states! {
+    State {
+        MainMenuHandler MainMenu(),
+        PlayingHandler Playing(level: u32)
+    }
+}
+
+type App = Data<(), ()>;
+
+impl MainMenuHandler for App {
+    fn handle_event(&mut self, _: WindowId, _: Event) -> Action<State> {
+        Action::Continue
+    }
+
+    fn handle_tick(&mut self, _: Duration) {}
+    fn handle_render(&self, _: f64) {}
+}
+
+impl PlayingHandler for App {
+    fn handle_event(&mut self, _: WindowId, _: Event, _: &u32) -> Action<State> {
+        Action::Continue
+    }
+
+    fn handle_tick(&mut self, _: Duration, _: &u32) {}
+    fn handle_render(&self, _: f64, _: &u32) {}
+}
+
+#[test]
+fn is_predicates_match_their_variant() {
+    assert!(State::MainMenu().is_main_menu());
+    assert!(!State::MainMenu().is_playing());
+
+    assert!(State::Playing(3).is_playing());
+    assert!(!State::Playing(3).is_main_menu());
+}
+
+#[test]
+fn discriminants_follow_declaration_order() {
+    assert_eq!(State::MainMenu().discriminant(), 0);
+    assert_eq!(State::Playing(3).discriminant(), 1);
+}
+
+#[test]
+fn try_from_builds_only_variants_without_arguments() {
+    assert!(State::try_from(0).unwrap().is_main_menu());
+    assert!(matches!(State::try_from(1), Err(InvalidDiscriminant(1))));
+    assert!(matches!(State::try_from(7), Err(InvalidDiscriminant(7))));
+}
@@ -0,0 +1,147 @@
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+use stateloop::app::{Data, Event, PhysicalSize, WindowId};
+use stateloop::config::{RenderMode, RunConfig};
+use stateloop::headless::HeadlessApp;
+use stateloop::state::Action;
+use stateloop::states;
+use stateloop::window::ResizeSource;
+
+#[derive(Default)]
+struct Counts {
+    ticks: u32,
+    renders: Cell<u32>,
+    queued: Cell<u32>,
+    resizes: RefCell<Vec<ResizeSource>>,
+}
+
+states! {
+    State {
+        ViewHandler View()
+    }
+}
+
+type App = Data<Counts, ()>;
+
+impl ViewHandler for App {
+    fn handle_event(&mut self, _: WindowId, _: Event) -> Action<State> {
+        Action::Continue
+    }
+
+    fn handle_tick(&mut self, _: Duration) {
+        self.data.ticks += 1;
+    }
+
+    fn handle_resize(&mut self, _: PhysicalSize<u32>, source: ResizeSource) {
+        self.data.resizes.borrow_mut().push(source);
+    }
+
+    fn handle_render(&self, _: f64) {
+        self.data.renders.set(self.data.renders.get() + 1);
+    }
+}
+
+fn run(mode: RenderMode) -> HeadlessApp<Counts, State> {
+    let config = RunConfig::new(60).render_mode(mode);
+    let mut app = HeadlessApp::with_config(Counts::default(), config, State::View());
+
+    // The first frame always renders.
+    app.step_frame(Duration::ZERO);
+    app.step_frame(Duration::ZERO);
+    assert_eq!(app.data().data.renders.get(), 1);
+
+    app
+}
+
+#[test]
+fn enqueued_renders_run_under_every_render_mode() {
+    for mode in [RenderMode::OnChange, RenderMode::OnRequest] {
+        let mut app = run(mode);
+
+        app.data_mut().enqueue_render(|data| {
+            data.data.queued.set(data.data.queued.get() + 1);
+        });
+        app.step_frame(Duration::ZERO);
+
+        assert_eq!(app.data().data.renders.get(), 2);
+        assert_eq!(app.data().data.queued.get(), 1);
+    }
+}
+
+#[test]
+fn initial_resize_fires_once() {
+    let mut app = HeadlessApp::new(Counts::default(), State::View());
+    app.step_frame(Duration::ZERO);
+    app.step_frame(Duration::ZERO);
+    assert_eq!(*app.data().data.resizes.borrow(), [ResizeSource::Initial]);
+
+    let config = RunConfig::new(60).initial_resize(false);
+    let mut app = HeadlessApp::with_config(Counts::default(), config, State::View());
+    app.step_frame(Duration::ZERO);
+    assert!(app.data().data.resizes.borrow().is_empty());
+}
+
+#[test]
+fn renders_outnumber_ticks_with_a_low_tick_rate() {
+    let config = RunConfig::new(10).render_cap(Some(120));
+    let mut app = HeadlessApp::with_config(Counts::default(), config, State::View());
+
+    // A quarter of a second at the render cap.
+    for _ in 0..30 {
+        app.step_frame(Duration::from_secs(1) / 120);
+    }
+
+    let counts = &app.data().data;
+    assert_eq!(counts.renders.get(), 30);
+    assert_eq!(counts.ticks, 2);
+}
+
+#[test]
+fn frames_are_paced_to_the_render_cap() {
+    let config = RunConfig::new(10).render_cap(Some(100));
+    let mut app = HeadlessApp::with_config(Counts::default(), config, State::View());
+    app.step_frame(Duration::from_millis(25));
+
+    assert_eq!(app.frame_pacing(Duration::ZERO), Duration::from_millis(10));
+    assert_eq!(
+        app.frame_pacing(Duration::from_millis(4)),
+        Duration::from_millis(6)
+    );
+
+    // By default the wait is until the next tick is due.
+    let mut app = HeadlessApp::with_config(Counts::default(), RunConfig::new(10), State::View());
+    app.step_frame(Duration::from_millis(25));
+    assert_eq!(app.frame_pacing(Duration::ZERO), Duration::from_millis(75));
+
+    let config = RunConfig::new(10).render_cap(None);
+    let app = HeadlessApp::with_config(Counts::default(), config, State::View());
+    assert_eq!(app.frame_pacing(Duration::ZERO), Duration::ZERO);
+}
+
+#[test]
+fn redraw_requests_within_a_frame_render_once() {
+    let mut app = run(RenderMode::OnRequest);
+
+    for _ in 0..5 {
+        app.data().request_redraw();
+    }
+
+    app.step_frame(Duration::ZERO);
+    app.step_frame(Duration::ZERO);
+
+    assert_eq!(app.data().data.renders.get(), 2);
+}
+
+#[test]
+fn re_enabling_rendering_redraws_under_on_request() {
+    let mut app = run(RenderMode::OnRequest);
+
+    app.data_mut().set_rendering_enabled(false);
+    app.step_frame(Duration::ZERO);
+
+    app.data_mut().set_rendering_enabled(true);
+    app.step_frame(Duration::ZERO);
+
+    assert_eq!(app.data().data.renders.get(), 2);
+}
@@ -0,0 +1,317 @@
+use std::time::{Duration, Instant};
+
+use stateloop::app::{Data, Event, LoopSnapshot, WindowId};
+use stateloop::config::{CatchupStrategy, RenderMode, RunConfig};
+use stateloop::harness::{LoopEvent, LoopTestHarness};
+use stateloop::headless::HeadlessApp;
+use stateloop::state::Action;
+use stateloop::states;
+
+states! {
+    State {
+        RunningHandler Running(),
+        SlowHandler Slow(),
+        FrozenHandler Frozen()
+    }
+}
+
+type App = Data<Vec<Duration>, ()>;
+
+impl RunningHandler for App {
+    fn handle_event(&mut self, _: WindowId, _: Event) -> Action<State> {
+        Action::Continue
+    }
+
+    fn handle_tick(&mut self, dt: Duration) {
+        self.split_mut().1.push(dt);
+    }
+
+    fn handle_render(&self, _: f64) {}
+}
+
+// Every tick takes longer than the 60 Hz timestep it simulates, and events
+// are slow to handle too.
+impl SlowHandler for App {
+    fn handle_event(&mut self, _: WindowId, _: Event) -> Action<State> {
+        std::thread::sleep(Duration::from_millis(20));
+        Action::Continue
+    }
+
+    fn handle_tick(&mut self, dt: Duration) {
+        std::thread::sleep(Duration::from_millis(20));
+        self.split_mut().1.push(dt);
+    }
+
+    fn handle_render(&self, _: f64) {}
+}
+
+impl FrozenHandler for App {
+    fn tick_rate(&self) -> Option<u32> {
+        Some(0)
+    }
+
+    fn handle_event(&mut self, _: WindowId, _: Event) -> Action<State> {
+        Action::Continue
+    }
+
+    fn handle_tick(&mut self, _: Duration) {}
+
+    fn handle_render(&self, _: f64) {}
+}
+
+fn ticks(app: &mut HeadlessApp<Vec<Duration>, State>) -> Vec<Duration> {
+    std::mem::take(app.data_mut().split_mut().1)
+}
+
+#[test]
+fn changing_fps_mid_run_changes_tick_cadence() {
+    let mut app = HeadlessApp::new(Vec::new(), State::Running());
+
+    app.step_frame(Duration::from_millis(1001));
+    let fast = ticks(&mut app);
+    assert_eq!(fast.len(), 60);
+    assert!(fast
+        .iter()
+        .all(|&dt| dt == Duration::from_secs_f64(1.0 / 60.0)));
+
+    app.data_mut().set_fps(15);
+    assert_eq!(app.data().target_fps(), 15);
+
+    app.step_frame(Duration::from_millis(1001));
+    let slow = ticks(&mut app);
+    assert_eq!(slow.len(), 15);
+    assert!(slow
+        .iter()
+        .all(|&dt| dt == Duration::from_secs_f64(1.0 / 15.0)));
+}
+
+#[test]
+fn raising_fps_does_not_burst_leftover_time() {
+    let mut app = HeadlessApp::with_config(Vec::new(), RunConfig::new(15), State::Running());
+
+    // Most of a 15 Hz timestep, which would be several 60 Hz ones.
+    app.step_frame(Duration::from_millis(60));
+    assert!(ticks(&mut app).is_empty());
+
+    app.data_mut().set_fps(60);
+    app.step_frame(Duration::ZERO);
+    assert!(ticks(&mut app).is_empty());
+
+    app.step_frame(Duration::from_millis(2));
+    assert_eq!(ticks(&mut app).len(), 1);
+}
+
+#[test]
+fn high_tick_rates_have_a_non_zero_timestep() {
+    let mut app = HeadlessApp::with_config(Vec::new(), RunConfig::new(2000), State::Running());
+    assert_eq!(app.data().tick_interval(), Duration::from_micros(500));
+
+    app.step_frame(Duration::from_millis(10));
+    assert_eq!(ticks(&mut app).len(), 20);
+}
+
+#[test]
+fn tick_render_ratio_counts_only_rendered_frames() {
+    let config = RunConfig::new(60).render_mode(RenderMode::OnChange);
+    let mut app = HeadlessApp::with_config(Vec::new(), config, State::Running());
+
+    // The first frame always renders; the next three change nothing.
+    app.step_frame(Duration::ZERO);
+
+    for _ in 0..3 {
+        app.step_frame(Duration::from_millis(17));
+    }
+
+    app.data_mut().mark_dirty();
+    app.step_frame(Duration::from_millis(17));
+
+    assert_eq!(ticks(&mut app).len(), 4);
+    assert_eq!(app.data().tick_render_ratio(), 2.0);
+}
+
+#[test]
+fn temporary_fps_accepts_an_unbounded_duration() {
+    let mut app = HeadlessApp::new(Vec::new(), State::Running());
+
+    app.data_mut().set_temporary_fps(30, Duration::MAX);
+    app.step_frame(Duration::from_millis(1001));
+
+    assert_eq!(app.data().target_fps(), 30);
+    assert_eq!(ticks(&mut app).len(), 30);
+}
+
+#[test]
+fn skip_first_delta_avoids_a_first_frame_burst() {
+    let config = RunConfig::new(60).skip_first_delta(true);
+    let mut app = HeadlessApp::with_config(Vec::new(), config, State::Running());
+
+    // A slow first frame, e.g. one that compiled shaders, runs no ticks.
+    app.step_frame(Duration::from_secs(1));
+    assert!(ticks(&mut app).is_empty());
+
+    app.step_frame(Duration::from_millis(51));
+    assert_eq!(ticks(&mut app).len(), 3);
+}
+
+#[test]
+fn advancing_runs_the_ticks_for_the_time_without_sleeping() {
+    let mut app = HeadlessApp::with_config(Vec::new(), RunConfig::new(50), State::Running());
+    let start = Instant::now();
+
+    assert!(app.advance(Duration::from_secs(10)));
+
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(ticks(&mut app).len(), 500);
+    assert_eq!(app.data().frame_count(), 500);
+}
+
+#[test]
+fn slow_ticks_keep_the_loop_running() {
+    let mut app = HeadlessApp::new(Vec::new(), State::Slow());
+
+    for _ in 0..5 {
+        assert!(app.step_frame(Duration::from_millis(20)));
+    }
+
+    assert!(!ticks(&mut app).is_empty());
+}
+
+#[test]
+fn long_frames_are_not_paced() {
+    // A slow tick makes the frame take longer than the render cap allows.
+    let config = RunConfig::new(60).render_cap(Some(120));
+    let mut app = HeadlessApp::with_config(Vec::new(), config, State::Slow());
+    let start = Instant::now();
+
+    assert!(app.step_frame(Duration::from_millis(20)));
+    assert_eq!(app.frame_pacing(start.elapsed()), Duration::ZERO);
+
+    // More than a timestep left to tick, as restored from a snapshot.
+    let mut app = HeadlessApp::new(Vec::new(), State::Running());
+    let mut snapshot = app.snapshot();
+    snapshot.accum = Duration::from_secs(1);
+    app.restore(snapshot);
+
+    assert_eq!(app.frame_pacing(Duration::ZERO), Duration::ZERO);
+}
+
+#[test]
+fn state_timings_include_event_handling() {
+    let mut app = HeadlessApp::new(Vec::new(), State::Slow());
+
+    app.step_event(Event::Focused(true));
+
+    let timings = app.data().state_timings();
+    assert_eq!(timings[0].0, "Slow");
+    assert!(timings[0].1 >= Duration::from_millis(20));
+}
+
+#[test]
+fn restoring_a_snapshot_resumes_where_it_was_taken() {
+    let mut app = HeadlessApp::new(Vec::new(), State::Running());
+
+    // One tick, with half a timestep left over.
+    app.step_frame(Duration::from_millis(25));
+    let snapshot: LoopSnapshot<State> = app.snapshot();
+    assert_eq!(ticks(&mut app).len(), 1);
+
+    app.step_frame(Duration::from_secs(1));
+    ticks(&mut app);
+
+    app.restore(snapshot);
+    assert!(matches!(app.current_state(), State::Running()));
+    assert_eq!(app.data().frame_count(), 1);
+    assert_eq!(
+        app.data().time_in_state(),
+        Duration::from_secs_f64(1.0 / 60.0)
+    );
+
+    // The leftover half timestep carries over.
+    app.step_frame(Duration::from_millis(9));
+    assert_eq!(ticks(&mut app).len(), 1);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn snapshots_round_trip_through_serde() {
+    let snapshot = LoopSnapshot {
+        state: 2u32,
+        stack: vec![1],
+        accum: Duration::from_millis(5),
+        frame: 40,
+        time_in_state: Duration::from_secs(3),
+    };
+
+    let json = serde_json::to_string(&snapshot).unwrap();
+    assert_eq!(
+        serde_json::from_str::<LoopSnapshot<u32>>(&json).unwrap(),
+        snapshot
+    );
+}
+
+#[test]
+fn harness_records_ticks_and_renders_in_order() {
+    let mut harness = LoopTestHarness::new(Vec::new(), RunConfig::new(60), State::Running());
+
+    assert!(harness.run_frames(3));
+    assert_eq!(
+        harness.frames(),
+        vec![vec![LoopEvent::Render, LoopEvent::Tick]; 3]
+    );
+    assert_eq!(harness.dropped_frames(), 0);
+}
+
+#[test]
+fn slow_ticks_fall_further_behind_without_a_clamp() {
+    let mut harness = LoopTestHarness::new(Vec::new(), RunConfig::new(60), State::Running())
+        .tick_cost(Duration::from_millis(20));
+
+    harness.run_frames(6);
+
+    // Each tick costs more than the timestep it simulates, so every frame
+    // has more to catch up on than the last.
+    let ticks: Vec<_> = harness
+        .frames()
+        .iter()
+        .map(|events| events.iter().filter(|&&e| e == LoopEvent::Tick).count())
+        .collect();
+
+    assert!(ticks.windows(2).all(|pair| pair[1] >= pair[0]));
+    assert!(ticks[5] > ticks[0]);
+    assert!(harness.frames()[5]
+        .iter()
+        .any(|e| matches!(e, LoopEvent::FellBehind(_))));
+}
+
+#[test]
+fn clamped_catch_up_runs_one_tick_per_frame_under_load() {
+    let config = RunConfig::new(60).catchup_strategy(CatchupStrategy::Clamp);
+    let mut harness = LoopTestHarness::new(Vec::new(), config, State::Running())
+        .tick_cost(Duration::from_millis(40));
+
+    harness.run_frames(6);
+
+    assert_eq!(harness.tick_count(), 6);
+    assert_eq!(harness.dropped_frames(), 0);
+    assert!(harness.frames()[5]
+        .iter()
+        .any(|e| matches!(e, LoopEvent::FellBehind(_))));
+}
+
+#[test]
+#[should_panic(expected = "the frame rate must be non-zero")]
+fn a_frame_rate_of_zero_is_rejected() {
+    RunConfig::<(), ()>::new(0);
+}
+
+#[test]
+#[should_panic(expected = "the render cap must be non-zero")]
+fn a_render_cap_of_zero_is_rejected() {
+    RunConfig::<(), ()>::new(60).render_cap(Some(0));
+}
+
+#[test]
+#[should_panic(expected = "Frozen has a tick rate of zero")]
+fn a_state_tick_rate_of_zero_is_rejected() {
+    HeadlessApp::new(Vec::new(), State::Frozen());
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use stateloop::app::{Data, Event, WindowId};
+use stateloop::headless::HeadlessApp;
+use stateloop::state::Action;
+use stateloop::states;
+
+pub enum Message {
+    Start,
+    Score(u32),
+    Quit,
+}
+
+states! {
+    State<Message> {
+        LobbyHandler Lobby(),
+        MatchHandler Match(score: u32)
+    }
+}
+
+type App = Data<(), ()>;
+
+impl LobbyHandler for App {
+    fn handle_event(&mut self, _: WindowId, _: Event) -> Action<State> {
+        Action::Continue
+    }
+
+    fn handle_user_event(&mut self, message: Message) -> Action<State> {
+        match message {
+            Message::Start => Action::Done(State::Match(0)),
+            _ => Action::Continue,
+        }
+    }
+
+    fn handle_tick(&mut self, _: Duration) {}
+    fn handle_render(&self, _: f64) {}
+}
+
+impl MatchHandler for App {
+    fn handle_event(&mut self, _: WindowId, _: Event, _: &u32) -> Action<State> {
+        Action::Continue
+    }
+
+    fn handle_user_event(&mut self, message: Message, score: &u32) -> Action<State> {
+        match message {
+            Message::Score(points) => Action::Done(State::Match(score + points)),
+            Message::Quit => Action::Quit,
+            Message::Start => Action::Continue,
+        }
+    }
+
+    fn handle_tick(&mut self, _: Duration, _: &u32) {}
+    fn handle_render(&self, _: f64, _: &u32) {}
+}
+
+#[test]
+fn user_events_reach_the_active_variant() {
+    let mut app = HeadlessApp::new((), State::Lobby());
+
+    // The lobby ignores scores, which only the match handles.
+    assert!(app.step_user_event(Message::Score(5)));
+    assert!(app.current_state().is_lobby());
+
+    assert!(app.step_user_event(Message::Start));
+    assert!(app.step_user_event(Message::Score(5)));
+    assert!(app.step_user_event(Message::Score(2)));
+    assert!(matches!(app.current_state(), State::Match(7)));
+
+    assert!(!app.step_user_event(Message::Quit));
+}